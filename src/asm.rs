@@ -1,107 +1,378 @@
 use anyhow::Result;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
+use xxhash_rust::xxh3::xxh3_64;
 use zip::write::FileOptions;
 use zip::ZipWriter;
 
 use crate::compile;
-use crate::config::SbConfig;
+use crate::config::{MergeStrategy, SbConfig};
+use crate::run;
+use crate::shade::Shader;
 
-pub fn assemble(config: &SbConfig, project_root: &Path) -> Result<PathBuf> {
-    let main_class = config
-        .project
-        .main_class
-        .as_deref()
-        .ok_or_else(|| anyhow::anyhow!("main-class is required in sb.toml for assembly"))?;
+/// Rules tried after those in `[assembly]`, following sbt-assembly's defaults.
+/// Anything they do not match keeps its first copy.
+const DEFAULT_RULES: &[(&str, MergeStrategy)] = &[
+    // Signatures of the original JARs do not hold for the merged one
+    ("META-INF/*.SF", MergeStrategy::Discard),
+    ("META-INF/*.DSA", MergeStrategy::Discard),
+    ("META-INF/*.RSA", MergeStrategy::Discard),
+    ("META-INF/INDEX.LIST", MergeStrategy::Discard),
+    ("META-INF/DEPENDENCIES", MergeStrategy::Discard),
+    ("**/module-info.class", MergeStrategy::Discard),
+    ("META-INF/services/**", MergeStrategy::Concat),
+    ("reference.conf", MergeStrategy::Concat),
+    ("application.conf", MergeStrategy::Concat),
+    ("**/LICENSE*", MergeStrategy::Rename),
+    ("**/LICENCE*", MergeStrategy::Rename),
+    ("**/NOTICE*", MergeStrategy::Rename),
+    ("**/COPYING*", MergeStrategy::Rename),
+    ("**/README*", MergeStrategy::Rename),
+];
 
+/// A directory of classes or a dependency JAR that goes into the assembly.
+struct Input {
+    label: String,
+    /// Appended to the names of its entries by the rename strategy; `None`
+    /// for the project's own classes, which keep their names
+    rename_suffix: Option<String>,
+    source: Source,
+}
+
+enum Source {
+    Dir(PathBuf),
+    Jar(PathBuf),
+}
+
+/// One input's copy of an entry that several inputs have. Only the copies a
+/// merge needs keep their bytes; the others are written as they are read.
+struct Copy {
+    input: usize,
+    hash: u64,
+    bytes: Option<Vec<u8>>,
+}
+
+impl Input {
+    fn entry_names(&self) -> Result<Vec<String>> {
+        match &self.source {
+            Source::Dir(dir) => {
+                let mut names = Vec::new();
+                for entry in WalkDir::new(dir).sort_by_file_name() {
+                    let entry = entry?;
+                    if !entry.file_type().is_dir() {
+                        names.push(dir_entry_name(dir, entry.path())?);
+                    }
+                }
+                Ok(names)
+            }
+            Source::Jar(path) => {
+                let archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+                Ok(archive.file_names().map(String::from).collect())
+            }
+        }
+    }
+
+    /// Read the entries one at a time, in the order of `entry_names`.
+    fn for_each_entry(&self, mut f: impl FnMut(String, Vec<u8>) -> Result<()>) -> Result<()> {
+        match &self.source {
+            Source::Dir(dir) => {
+                for entry in WalkDir::new(dir).sort_by_file_name() {
+                    let entry = entry?;
+                    if !entry.file_type().is_dir() {
+                        f(dir_entry_name(dir, entry.path())?, std::fs::read(entry.path())?)?;
+                    }
+                }
+            }
+            Source::Jar(path) => {
+                let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+                for i in 0..archive.len() {
+                    let mut entry = archive.by_index(i)?;
+                    let name = entry.name().to_string();
+                    let mut bytes = Vec::new();
+                    entry.read_to_end(&mut bytes)?;
+                    f(name, bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn dir_entry_name(dir: &Path, path: &Path) -> Result<String> {
+    Ok(path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/"))
+}
+
+pub fn assemble(config: &SbConfig, project_root: &Path, main: Option<&str>, report: bool) -> Result<PathBuf> {
+    let shader = Shader::new(&config.assembly.shade)?;
     let result = compile::compile(config, project_root)?;
+    let main_class = run::main_class(config, project_root, &result.resolved, main)?;
 
     let output_path = project_root
         .join(".sb")
         .join(format!("{}-{}-assembly.jar", config.project.name, config.project.version));
 
-    let file = std::fs::File::create(&output_path)?;
-    let mut zip = ZipWriter::new(file);
-    let mut seen = HashSet::new();
+    // Compiled classes and resources, then those of upstream workspace members
+    let mut inputs = Vec::new();
+    let mut roots = vec![(project_root.to_path_buf(), None)];
+    roots.extend(config.upstream.iter().map(|root| {
+        let member = root.file_name().unwrap_or_default().to_string_lossy().to_string();
+        (root.clone(), Some(member))
+    }));
+    let output_dirs = roots.into_iter().flat_map(|(root, member)| {
+        [
+            (SbConfig::classes_dir(&root), ".sb/classes", member.clone()),
+            (SbConfig::resource_output_dir(&root), ".sb/resources", member),
+        ]
+    });
+    for (dir, dir_label, member) in output_dirs {
+        if !dir.exists() {
+            continue;
+        }
+        inputs.push(Input {
+            label: match &member {
+                Some(m) => format!("{m}/{dir_label}"),
+                None => dir_label.to_string(),
+            },
+            rename_suffix: member,
+            source: Source::Dir(dir),
+        });
+    }
+
+    // Dependency JARs
+    for jar_path in result.resolved.run_classpath().split(':') {
+        if jar_path.is_empty() || !jar_path.ends_with(".jar") {
+            continue;
+        }
+        let path = Path::new(jar_path);
+        inputs.push(Input {
+            label: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            rename_suffix: Some(path.file_stem().unwrap_or_default().to_string_lossy().to_string()),
+            source: Source::Jar(path.to_path_buf()),
+        });
+    }
 
+    // The assembly gets its own manifest
+    let assembled_name = |name: &str| {
+        if name.ends_with('/') || name == "META-INF/MANIFEST.MF" {
+            None
+        } else if shader.is_empty() {
+            Some(name.to_string())
+        } else {
+            Some(shader.entry_name(name))
+        }
+    };
+
+    // Count the copies of each entry first, so that only those of entries in
+    // more than one input need to be held in memory
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut readable = Vec::new();
+    for input in inputs {
+        let names = match input.entry_names() {
+            Ok(names) => names,
+            // Not a readable JAR
+            Err(_) if matches!(input.source, Source::Jar(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        for name in names.iter().filter_map(|n| assembled_name(n)) {
+            *counts.entry(name).or_default() += 1;
+        }
+        readable.push(input);
+    }
+    let inputs = readable;
+
+    let tmp_path = output_path.with_extension("jar.tmp");
+    let mut zip = ZipWriter::new(std::fs::File::create(&tmp_path)?);
+    let mut seen = HashSet::new();
     let options = FileOptions::<()>::default()
         .compression_method(zip::CompressionMethod::Deflated);
+    let mut write = |zip: &mut ZipWriter<std::fs::File>, name: &str, bytes: &[u8]| -> Result<()> {
+        // A renamed copy may clash with an entry of that name
+        if seen.insert(name.to_string()) {
+            zip.start_file(name, options)?;
+            zip.write_all(bytes)?;
+        }
+        Ok(())
+    };
 
     // Write manifest
     let manifest = format!(
         "Manifest-Version: 1.0\r\nMain-Class: {}\r\n\r\n",
         main_class
     );
-    let manifest_path = "META-INF/MANIFEST.MF";
-    zip.start_file(manifest_path, options)?;
-    zip.write_all(manifest.as_bytes())?;
-    seen.insert(manifest_path.to_string());
-    seen.insert("META-INF/".to_string());
-
-    // Add compiled classes
-    let classes_dir = SbConfig::classes_dir(project_root);
-    for entry in WalkDir::new(&classes_dir) {
-        let entry = entry?;
-        if entry.file_type().is_dir() {
-            continue;
+    write(&mut zip, "META-INF/MANIFEST.MF", manifest.as_bytes())?;
+
+    // Entries in several inputs, in the order they were first seen
+    let mut order = Vec::new();
+    let mut copies: HashMap<String, Vec<Copy>> = HashMap::new();
+    for (index, input) in inputs.iter().enumerate() {
+        input.for_each_entry(|raw_name, bytes| {
+            let Some(name) = assembled_name(&raw_name) else {
+                return Ok(());
+            };
+            let bytes = if shader.is_empty() { bytes } else { shader.entry_bytes(&raw_name, bytes)? };
+            let strategy = strategy_for(config, &name);
+            if counts[&name] == 1 {
+                if strategy != MergeStrategy::Discard {
+                    write(&mut zip, &name, &bytes)?;
+                }
+                return Ok(());
+            }
+
+            let copies = copies.entry(name.clone()).or_insert_with_key(|name| {
+                order.push(name.clone());
+                Vec::new()
+            });
+            let hash = xxh3_64(&bytes);
+            let bytes = match strategy {
+                MergeStrategy::Discard => None,
+                MergeStrategy::First | MergeStrategy::Error => {
+                    if copies.is_empty() {
+                        write(&mut zip, &name, &bytes)?;
+                    }
+                    None
+                }
+                MergeStrategy::Rename => {
+                    let renamed = match &input.rename_suffix {
+                        Some(suffix) => format!("{name}_{suffix}"),
+                        None => name.clone(),
+                    };
+                    write(&mut zip, &renamed, &bytes)?;
+                    None
+                }
+                MergeStrategy::Last => {
+                    for copy in copies.iter_mut() {
+                        copy.bytes = None;
+                    }
+                    Some(bytes)
+                }
+                MergeStrategy::Concat => Some(bytes),
+            };
+            copies.push(Copy { input: index, hash, bytes });
+            Ok(())
+        })?;
+    }
+
+    let mut conflicts = Vec::new();
+    let mut errors = Vec::new();
+    for name in &order {
+        let copies = &copies[name];
+        let strategy = strategy_for(config, name);
+        let identical = copies.iter().all(|c| c.hash == copies[0].hash);
+        let labels: Vec<&str> = copies.iter().map(|c| inputs[c.input].label.as_str()).collect();
+        let note = if identical { ", identical" } else { "" };
+        conflicts.push(format!("  {name} ({}{note}): {}", strategy.as_str(), labels.join(", ")));
+        match strategy {
+            MergeStrategy::Last => {
+                if let Some(bytes) = &copies[copies.len() - 1].bytes {
+                    write(&mut zip, name, bytes)?;
+                }
+            }
+            MergeStrategy::Concat => {
+                let parts = copies.iter().filter_map(|c| c.bytes.as_deref());
+                let joined = if glob_matches("META-INF/services/**", name) {
+                    concat_services(parts)
+                } else {
+                    concat(parts)
+                };
+                write(&mut zip, name, &joined)?;
+            }
+            MergeStrategy::Error if !identical => errors.push(format!("  {name}: {}", labels.join(", "))),
+            _ => {}
         }
-        let rel = entry
-            .path()
-            .strip_prefix(&classes_dir)?
-            .to_string_lossy()
-            .replace('\\', "/");
-        if seen.contains(&rel) {
-            continue;
+    }
+
+    if report {
+        if conflicts.is_empty() {
+            println!("No conflicting entries");
+        } else {
+            println!("{} conflicting entries:", conflicts.len());
+            for line in &conflicts {
+                println!("{line}");
+            }
         }
-        zip.start_file(&rel, options)?;
-        let data = std::fs::read(entry.path())?;
-        zip.write_all(&data)?;
-        seen.insert(rel);
+    }
+    if !errors.is_empty() {
+        drop(zip);
+        std::fs::remove_file(&tmp_path)?;
+        anyhow::bail!(
+            "entries differ between inputs and their merge strategy is \"error\":\n{}",
+            errors.join("\n")
+        );
     }
 
-    // Merge dependency JARs
-    for jar_path in result.resolved.user_cp.split(':') {
-        if jar_path.is_empty() || !jar_path.ends_with(".jar") {
-            continue;
+    zip.finish()?;
+    std::fs::rename(&tmp_path, &output_path)?;
+    Ok(output_path)
+}
+
+fn concat<'a>(parts: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut joined = Vec::new();
+    for part in parts {
+        if !joined.is_empty() && !joined.ends_with(b"\n") {
+            joined.push(b'\n');
         }
-        let jar_file = match std::fs::File::open(jar_path) {
-            Ok(f) => f,
-            Err(_) => continue,
-        };
-        let mut archive = match zip::ZipArchive::new(jar_file) {
-            Ok(a) => a,
-            Err(_) => continue,
-        };
-        for i in 0..archive.len() {
-            let mut entry = archive.by_index(i)?;
-            let name = entry.name().to_string();
+        joined.extend_from_slice(part);
+    }
+    joined
+}
 
-            // Skip directories, manifests, and signature files
-            if name.ends_with('/') {
-                continue;
-            }
-            if name == "META-INF/MANIFEST.MF" {
-                continue;
-            }
-            if name.starts_with("META-INF/")
-                && (name.ends_with(".SF") || name.ends_with(".RSA") || name.ends_with(".DSA"))
-            {
-                continue;
+/// Service files list one provider per line, and several JARs often name the
+/// same one, so each line is kept once.
+fn concat_services<'a>(parts: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut seen = HashSet::new();
+    let mut joined = Vec::new();
+    for part in parts {
+        for line in String::from_utf8_lossy(part).lines() {
+            let line = line.trim();
+            if !line.is_empty() && seen.insert(line.to_string()) {
+                joined.extend_from_slice(line.as_bytes());
+                joined.push(b'\n');
             }
-            if seen.contains(&name) {
-                continue;
+        }
+    }
+    joined
+}
+
+/// The first rule from `[assembly]` or the built-in ones whose path matches.
+fn strategy_for(config: &SbConfig, name: &str) -> MergeStrategy {
+    let configured = config.assembly.merge.iter().map(|r| (r.path.as_str(), r.strategy));
+    configured
+        .chain(DEFAULT_RULES.iter().copied())
+        .find(|(pattern, _)| glob_matches(pattern, name))
+        .map_or(MergeStrategy::First, |(_, strategy)| strategy)
+}
+
+/// Whether `path` matches `pattern`, ignoring ASCII case. `*` and `?` match
+/// within a directory, and `**` any number of directories.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => {
+                match_segment(first.as_bytes(), segment.as_bytes()) && match_segments(rest, path_rest)
             }
+            None => false,
+        },
+    }
+}
 
-            zip.start_file(&name, options)?;
-            let mut buf = Vec::new();
-            entry.read_to_end(&mut buf)?;
-            zip.write_all(&buf)?;
-            seen.insert(name);
+fn match_segment(pattern: &[u8], s: &[u8]) -> bool {
+    match (pattern.split_first(), s.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            match_segment(rest, s) || (!s.is_empty() && match_segment(pattern, &s[1..]))
         }
+        (Some((b'?', rest)), Some((_, s_rest))) => match_segment(rest, s_rest),
+        (Some((c, rest)), Some((d, s_rest))) => c.eq_ignore_ascii_case(d) && match_segment(rest, s_rest),
+        _ => false,
     }
-
-    zip.finish()?;
-    Ok(output_path)
 }
@@ -0,0 +1,120 @@
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::cache;
+
+/// A small Scala program that ships inside the sb binary and is compiled on
+/// first use (e.g. the `sb test` runner).
+pub struct SupportProgram {
+    pub name: &'static str,
+    pub source: &'static str,
+}
+
+pub const TEST_RUNNER: SupportProgram = SupportProgram {
+    name: "test-runner",
+    source: include_str!("scala/TestRunner.scala"),
+};
+
+pub const COMPILE_SERVER: SupportProgram = SupportProgram {
+    name: "compile-server",
+    source: include_str!("scala/CompileServer.scala"),
+};
+
+/// Compile a support program with the given Scala compiler, caching the output
+/// under `~/.cache/sb/bootstrap`. Returns the directory holding its classes.
+pub fn ensure_compiled(
+    program: &SupportProgram,
+    scala_version: &str,
+    compiler_cp: &str,
+    classpath: &str,
+) -> Result<PathBuf> {
+    let hash = cache::hash_strings(&[scala_version.to_string(), program.source.to_string()]);
+    let root = dirs::cache_dir()
+        .context("could not determine cache directory")?
+        .join("sb")
+        .join("bootstrap");
+    let out_dir = root.join(format!("{}-{scala_version}-{hash:016x}", program.name));
+    if out_dir.exists() {
+        return Ok(out_dir);
+    }
+    // Don't retry a compile that already failed for these sources with this
+    // JDK on every build; its first line records the JDK
+    let failed_marker = root.join(format!("{}-{scala_version}-{hash:016x}.failed", program.name));
+    let jdk = java_identity();
+    if let Ok(marker) = std::fs::read_to_string(&failed_marker) {
+        match marker.split_once('\n') {
+            Some((marker_jdk, reason)) if marker_jdk == jdk => {
+                bail!("sb {} does not compile with Scala {scala_version}:\n{reason}", program.name);
+            }
+            _ => {
+                let _ = std::fs::remove_file(&failed_marker);
+            }
+        }
+    }
+
+    // Compile into a scratch dir and rename, so an interrupted build never
+    // leaves a half-populated output behind.
+    let tmp_dir = root.join(format!(
+        "{}-{scala_version}-{hash:016x}.tmp{}",
+        program.name,
+        std::process::id()
+    ));
+    if tmp_dir.exists() {
+        std::fs::remove_dir_all(&tmp_dir)?;
+    }
+    std::fs::create_dir_all(&tmp_dir)?;
+    let src = tmp_dir.join(format!("{}.scala", program.name));
+    std::fs::write(&src, program.source)?;
+
+    eprintln!("Compiling sb {}...", program.name);
+    let output = Command::new("java")
+        .arg("--sun-misc-unsafe-memory-access=allow")
+        .arg("-cp")
+        .arg(compiler_cp)
+        .arg("dotty.tools.dotc.Main")
+        .arg("-classpath")
+        .arg(classpath)
+        .arg("-d")
+        .arg(&tmp_dir)
+        .arg(&src)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+        let reason = String::from_utf8_lossy(&output.stderr).to_string();
+        let _ = std::fs::write(&failed_marker, format!("{jdk}\n{reason}"));
+        bail!("failed to compile sb {}:\n{reason}", program.name);
+    }
+    std::fs::remove_file(&src)?;
+
+    if let Err(e) = std::fs::rename(&tmp_dir, &out_dir) {
+        // Another sb process may have won the race
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+        if !out_dir.exists() {
+            return Err(e.into());
+        }
+    }
+    Ok(out_dir)
+}
+
+/// The `java` on `PATH`, by resolved path and modification time, so that
+/// installing or switching JDKs gives a different identity.
+fn java_identity() -> String {
+    let exe = format!("java{}", std::env::consts::EXE_SUFFIX);
+    let java = std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(&exe))
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok());
+    let Some(java) = java else {
+        return String::from("no java");
+    };
+    let modified = std::fs::metadata(&java)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    format!("{} {modified}", java.display())
+}
@@ -67,6 +67,25 @@ pub fn hash_sources_per_file(
     Ok((hashes, files))
 }
 
+/// Per-file hashes of every file under `dir`, keyed by path relative to it.
+pub fn hash_files_per_file(dir: &Path) -> Result<HashMap<String, u64>> {
+    let mut hashes = HashMap::new();
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let content = std::fs::read(entry.path())?;
+        let rel = entry
+            .path()
+            .strip_prefix(dir)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .replace('\\', "/");
+        hashes.insert(rel, xxhash_rust::xxh3::xxh3_64(&content));
+    }
+    Ok(hashes)
+}
+
 /// Diff two per-file hash maps. Returns (changed, added, deleted) relative paths.
 pub fn diff_hashes(
     old: &HashMap<String, u64>,
@@ -5,8 +5,15 @@ use std::process::Command;
 
 use crate::cache;
 use crate::config::SbConfig;
+use crate::diagnostics::{self, MessageFormat};
+#[cfg(unix)]
+use crate::daemon;
 use crate::resolve::{self, ResolvedClasspath};
+use crate::resources;
+use crate::tasty::api::Api;
 use crate::tasty::deps::{self, IncrementalState};
+use crate::transaction::Transaction;
+use crate::workspace::Member;
 
 pub struct CompileResult {
     pub resolved: ResolvedClasspath,
@@ -15,6 +22,7 @@ pub struct CompileResult {
 pub fn compile(config: &SbConfig, project_root: &Path) -> Result<CompileResult> {
     let src_dir = SbConfig::source_dir(project_root);
     let classes_dir = SbConfig::classes_dir(project_root);
+    resources::sync(config, project_root)?;
 
     // Parallel: resolve deps + hash sources (per-file)
     let (resolved, src_result) = std::thread::scope(|s| {
@@ -32,7 +40,7 @@ pub fn compile(config: &SbConfig, project_root: &Path) -> Result<CompileResult>
         bail!("no .scala source files found in {}", src_dir.display());
     }
 
-    let dep_hash_str = resolve::dep_hash(config).to_string();
+    let dep_hash_str = inputs_hash(config, &resolved);
 
     // Try incremental compilation
     if let Some(old_state) = IncrementalState::load(project_root) {
@@ -67,7 +75,7 @@ pub fn compile(config: &SbConfig, project_root: &Path) -> Result<CompileResult>
     let cached_src = cache::read_cache(project_root, "src-hash");
     let cached_dep = cache::read_cache(project_root, "dep-hash");
     if cached_src.as_deref() == Some(&agg_hash_str)
-        && cached_dep.as_deref() == Some(&dep_hash_str)
+        && cached_dep == Some(resolve::dep_hash(config).to_string())
         && classes_dir.exists()
         && std::fs::read_dir(&classes_dir)?.next().is_some()
     {
@@ -75,6 +83,7 @@ pub fn compile(config: &SbConfig, project_root: &Path) -> Result<CompileResult>
         // Migrate: save incremental state for next time
         let tasty_files = deps::scan_classes_dir(&classes_dir, project_root)?;
         let state = IncrementalState {
+            version: deps::STATE_VERSION,
             source_hashes: new_hashes,
             tasty_files,
             dep_hash: dep_hash_str,
@@ -94,6 +103,38 @@ pub fn compile(config: &SbConfig, project_root: &Path) -> Result<CompileResult>
     )
 }
 
+/// Compile workspace members in the given order, typically from `Workspace::build_order`.
+pub fn compile_workspace(members: &[&Member]) -> Result<()> {
+    for member in members {
+        eprintln!("[{}]", member.name);
+        compile(&member.config, &member.dir)?;
+    }
+    Ok(())
+}
+
+/// Hash of everything outside the sources that affects compilation output:
+/// the declared dependencies and the JARs they resolved to (which can change
+/// with sb.lock alone), plus the public API of upstream workspace members,
+/// so that an API change upstream recompiles downstream members.
+fn inputs_hash(config: &SbConfig, resolved: &ResolvedClasspath) -> String {
+    let mut items = vec![resolve::dep_hash(config).to_string(), resolved.compile_classpath()];
+    for root in &config.upstream {
+        match IncrementalState::load(root) {
+            Some(state) => {
+                let mut apis: Vec<String> = state
+                    .tasty_files
+                    .iter()
+                    .map(|t| format!("{}={}", t.tasty_path, t.api_hash))
+                    .collect();
+                apis.sort();
+                items.extend(apis);
+            }
+            None => items.push(format!("{}=unbuilt", root.display())),
+        }
+    }
+    cache::hash_strings(&items).to_string()
+}
+
 fn full_compile(
     config: &SbConfig,
     project_root: &Path,
@@ -103,27 +144,26 @@ fn full_compile(
     new_hashes: &std::collections::HashMap<String, u64>,
     dep_hash_str: &str,
 ) -> Result<CompileResult> {
-    // Clean classes dir for fresh compile
-    if classes_dir.exists() {
-        std::fs::remove_dir_all(classes_dir)?;
-    }
-    std::fs::create_dir_all(classes_dir)?;
-
     eprintln!(
         "Compiling {} source file{}...",
         sources.len(),
         if sources.len() == 1 { "" } else { "s" }
     );
 
-    invoke_dotc(config, resolved, classes_dir, sources, None)?;
+    let cp = resolve::join_classpath(&[&config.upstream_classpath(), &resolved.compile_classpath()]);
+    // Fresh compile into the staging dir, so that a failure leaves the old classes in place
+    let tx = Transaction::begin(project_root)?;
+    invoke_dotc(config, project_root, &resolved.compiler_cp, &cp, tx.staging_dir()?, sources)?;
+    tx.replace_classes()?;
 
     // Save incremental state
     let agg_hash = cache::hash_sources(&SbConfig::source_dir(project_root))?.0;
     cache::write_cache(project_root, "src-hash", &agg_hash.to_string())?;
-    cache::write_cache(project_root, "dep-hash", dep_hash_str)?;
+    cache::write_cache(project_root, "dep-hash", &resolve::dep_hash(config).to_string())?;
 
     let tasty_files = deps::scan_classes_dir(classes_dir, project_root)?;
     let state = IncrementalState {
+        version: deps::STATE_VERSION,
         source_hashes: new_hashes.clone(),
         tasty_files,
         dep_hash: dep_hash_str.to_string(),
@@ -149,18 +189,84 @@ fn incremental_compile(
     deleted: &[String],
     all_sources: &[PathBuf],
 ) -> Result<CompileResult> {
+    // A failed round undoes the earlier ones too, as their output assumes the later rounds succeed
+    let mut tx = Transaction::begin(project_root)?;
+    let rounds = compile_rounds(
+        config,
+        project_root,
+        resolved,
+        classes_dir,
+        new_hashes,
+        dep_hash_str,
+        old_state,
+        changed,
+        added,
+        deleted,
+        all_sources,
+        &mut tx,
+    );
+    let state = match rounds {
+        Ok(state) => state,
+        Err(e) => {
+            if let Err(restore) = tx.rollback() {
+                eprintln!("warning: failed to restore {}: {restore}", classes_dir.display());
+            }
+            return Err(e);
+        }
+    };
+    tx.commit()?;
+
+    let state = match state {
+        Some(state) => state,
+        // Only deletions, with nothing depending on the deleted files
+        None => IncrementalState {
+            version: deps::STATE_VERSION,
+            source_hashes: new_hashes.clone(),
+            tasty_files: deps::scan_classes_dir(classes_dir, project_root)?,
+            dep_hash: dep_hash_str.to_string(),
+        },
+    };
+    state.save(project_root)?;
+
+    // Save final aggregate hash for backward compat
+    let agg_hash = cache::hash_sources(&SbConfig::source_dir(project_root))?.0;
+    cache::write_cache(project_root, "src-hash", &agg_hash.to_string())?;
+    cache::write_cache(project_root, "dep-hash", &resolve::dep_hash(config).to_string())?;
+
+    Ok(CompileResult {
+        resolved: resolved.clone(),
+    })
+}
+
+/// Compile the changed sources, then the dependents of API changes, round
+/// after round until the API stops changing. Returns the state after the
+/// last round, if any ran.
+#[allow(clippy::too_many_arguments)]
+fn compile_rounds(
+    config: &SbConfig,
+    project_root: &Path,
+    resolved: &ResolvedClasspath,
+    classes_dir: &Path,
+    new_hashes: &std::collections::HashMap<String, u64>,
+    dep_hash_str: &str,
+    old_state: &IncrementalState,
+    changed: &[String],
+    added: &[String],
+    deleted: &[String],
+    all_sources: &[PathBuf],
+    tx: &mut Transaction,
+) -> Result<Option<IncrementalState>> {
     let rev_deps = old_state.reverse_dep_map();
 
-    // Collect old API hashes for all source files
-    let mut old_api_hashes: std::collections::HashMap<String, Option<u64>> =
-        std::collections::HashMap::new();
+    // Collect old APIs for all source files
+    let mut old_apis: std::collections::HashMap<String, Api> = std::collections::HashMap::new();
     for src in old_state.source_hashes.keys() {
-        old_api_hashes.insert(src.clone(), old_state.api_hash_for_source(src));
+        old_apis.insert(src.clone(), old_state.api_for_source(src));
     }
 
     // Remove stale class/tasty files for deleted sources
     for del_src in deleted {
-        remove_class_files_for_source(del_src, old_state, classes_dir);
+        remove_class_files_for_source(del_src, old_state, classes_dir, tx)?;
     }
 
     // Fixed-point loop
@@ -176,6 +282,7 @@ fn incremental_compile(
         }
     }
 
+    let mut latest = None;
     let mut round = 0;
     loop {
         round += 1;
@@ -185,7 +292,7 @@ fn incremental_compile(
 
         // Remove stale class/tasty files for files we're about to recompile
         for src in &to_recompile {
-            remove_class_files_for_source(src, old_state, classes_dir);
+            remove_class_files_for_source(src, old_state, classes_dir, tx)?;
         }
 
         // Resolve source paths
@@ -221,14 +328,22 @@ fn incremental_compile(
             );
         }
 
-        // Invoke dotc with classes_dir on classpath so compiler sees unchanged files
-        invoke_dotc(config, resolved, classes_dir, &compile_sources, Some(classes_dir))?;
+        // Invoke dotc with classes_dir on classpath so compiler sees unchanged
+        // files, writing to the staging dir until it succeeds
+        let cp = resolve::join_classpath(&[
+            &classes_dir.display().to_string(),
+            &config.upstream_classpath(),
+            &resolved.compile_classpath(),
+        ]);
+        invoke_dotc(config, project_root, &resolved.compiler_cp, &cp, tx.staging_dir()?, &compile_sources)?;
+        tx.install()?;
 
         // Re-scan tasty files to get new API hashes
         let new_tasty_files = deps::scan_classes_dir(classes_dir, project_root)?;
 
         // Build new state temporarily to check API changes
         let tmp_state = IncrementalState {
+            version: deps::STATE_VERSION,
             source_hashes: new_hashes.clone(),
             tasty_files: new_tasty_files,
             dep_hash: dep_hash_str.to_string(),
@@ -239,34 +354,23 @@ fn incremental_compile(
         let mut next_round: HashSet<String> = HashSet::new();
 
         for src in &to_recompile {
-            let old_api = old_api_hashes.get(src).copied().flatten();
-            let new_api = tmp_state.api_hash_for_source(src);
-            if old_api != new_api {
-                // API changed — add dependents to next round
-                if let Some(dependents) = new_rev_deps.get(src) {
-                    for dep in dependents {
-                        // Don't re-add files we just compiled in this round
-                        if !to_recompile.contains(dep) {
-                            next_round.insert(dep.clone());
-                        }
-                    }
-                }
-                // Also check old rev deps
-                if let Some(dependents) = rev_deps.get(src) {
-                    for dep in dependents {
-                        if !to_recompile.contains(dep) {
-                            next_round.insert(dep.clone());
-                        }
+            let new_api = tmp_state.api_for_source(src);
+            let changes = old_apis.get(src).cloned().unwrap_or_default().changes(&new_api);
+            if !changes.is_empty() {
+                // API changed — add the dependents using a changed name to next round
+                let dependents = new_rev_deps.get(src).into_iter().chain(rev_deps.get(src)).flatten();
+                for dep in dependents {
+                    // Don't re-add files we just compiled in this round
+                    if !to_recompile.contains(dep) && tmp_state.is_affected(dep, &changes) {
+                        next_round.insert(dep.clone());
                     }
                 }
             }
-            // Update old_api_hashes for next round comparison
-            old_api_hashes.insert(src.clone(), new_api);
+            // Update old_apis for next round comparison
+            old_apis.insert(src.clone(), new_api);
         }
 
-        // Save state after each round (so we have latest tasty info)
-        tmp_state.save(project_root)?;
-
+        latest = Some(tmp_state);
         to_recompile = next_round;
 
         // Safety: prevent infinite loops
@@ -276,32 +380,24 @@ fn incremental_compile(
         }
     }
 
-    // Save final aggregate hash for backward compat
-    let agg_hash = cache::hash_sources(&SbConfig::source_dir(project_root))?.0;
-    cache::write_cache(project_root, "src-hash", &agg_hash.to_string())?;
-    cache::write_cache(project_root, "dep-hash", dep_hash_str)?;
-
-    Ok(CompileResult {
-        resolved: resolved.clone(),
-    })
+    Ok(latest)
 }
 
 fn remove_class_files_for_source(
     source_rel: &str,
     old_state: &IncrementalState,
     classes_dir: &Path,
-) {
+    tx: &mut Transaction,
+) -> Result<()> {
     // Find tasty files from this source
     for info in &old_state.tasty_files {
         if info.source_file == source_rel {
             let stem = info.tasty_path.trim_end_matches(".tasty");
             // Remove .tasty, .class, and inner class files (Foo$.class, etc.)
             let tasty = classes_dir.join(&info.tasty_path);
-            let _ = std::fs::remove_file(&tasty);
-            let class = classes_dir.join(format!("{stem}.class"));
-            let _ = std::fs::remove_file(&class);
-            let dollar_class = classes_dir.join(format!("{stem}$.class"));
-            let _ = std::fs::remove_file(&dollar_class);
+            tx.remove(&tasty)?;
+            tx.remove(&classes_dir.join(format!("{stem}.class")))?;
+            tx.remove(&classes_dir.join(format!("{stem}$.class")))?;
 
             // Also try to remove common inner class patterns
             if let Some(parent) = tasty.parent() {
@@ -314,45 +410,131 @@ fn remove_class_files_for_source(
                     for entry in entries.flatten() {
                         let name = entry.file_name().to_string_lossy().to_string();
                         if name.starts_with(&format!("{prefix}$")) && name.ends_with(".class") {
-                            let _ = std::fs::remove_file(entry.path());
+                            tx.remove(&entry.path())?;
                         }
                     }
                 }
             }
         }
     }
+    Ok(())
+}
+
+/// Compile `src/test/scala` into `.sb/test-classes`, with the main classes and
+/// test dependencies on the classpath. Recompiles everything whenever the test
+/// sources, the main sources or the dependencies change.
+pub fn compile_tests(config: &SbConfig, project_root: &Path, resolved: &ResolvedClasspath) -> Result<()> {
+    let src_dir = SbConfig::test_source_dir(project_root);
+    let classes_dir = SbConfig::classes_dir(project_root);
+    let test_classes_dir = SbConfig::test_classes_dir(project_root);
+
+    let (test_hash, sources) = cache::hash_sources(&src_dir)?;
+    if sources.is_empty() {
+        bail!("no .scala test files found in {}", src_dir.display());
+    }
+
+    let key = cache::hash_strings(&[
+        test_hash.to_string(),
+        cache::read_cache(project_root, "src-hash").unwrap_or_default(),
+        inputs_hash(config, resolved),
+        resolved.test_cp.clone(),
+    ])
+    .to_string();
+    if cache::read_cache(project_root, "test-src-hash").as_deref() == Some(&key)
+        && test_classes_dir.exists()
+    {
+        eprintln!("Nothing to compile (tests).");
+        return Ok(());
+    }
+
+    if test_classes_dir.exists() {
+        std::fs::remove_dir_all(&test_classes_dir)?;
+    }
+    std::fs::create_dir_all(&test_classes_dir)?;
+
+    eprintln!(
+        "Compiling {} test source file{}...",
+        sources.len(),
+        if sources.len() == 1 { "" } else { "s" }
+    );
+
+    let cp = resolve::join_classpath(&[
+        &classes_dir.display().to_string(),
+        &config.upstream_classpath(),
+        &resolved.main_classpath(),
+        &resolved.test_cp,
+    ]);
+    invoke_dotc(config, project_root, &resolved.compiler_cp, &cp, &test_classes_dir, &sources)?;
+
+    cache::write_cache(project_root, "test-src-hash", &key)?;
+    Ok(())
 }
 
 fn invoke_dotc(
     config: &SbConfig,
-    resolved: &ResolvedClasspath,
+    project_root: &Path,
+    compiler_cp: &str,
+    classpath: &str,
     classes_dir: &Path,
     sources: &[PathBuf],
-    extra_cp_dir: Option<&Path>,
 ) -> Result<()> {
+    let mut args: Vec<String> = vec![
+        "-classpath".to_string(),
+        classpath.to_string(),
+        "-d".to_string(),
+        classes_dir.display().to_string(),
+    ];
+    args.extend(config.project.scalac_options.iter().cloned());
+    args.extend(sources.iter().map(|s| s.display().to_string()));
+
+    #[cfg(unix)]
+    if daemon::enabled()
+        && let Some(stream) = daemon::connect(&config.project.scala_version, compiler_cp)
+    {
+        // The daemon does not share our working directory, so pin the root
+        // that SOURCEFILE attributes in .tasty files are relative to.
+        let mut daemon_args = vec![
+            "-sourceroot".to_string(),
+            project_root.display().to_string(),
+        ];
+        daemon_args.extend(args);
+        let (success, output) = daemon::compile(stream, &daemon_args)?;
+        return report(config, success, &output);
+    }
+
     let mut cmd = Command::new("java");
     cmd.arg("--sun-misc-unsafe-memory-access=allow");
-    cmd.arg("-cp").arg(&resolved.compiler_cp);
+    cmd.arg("-cp").arg(compiler_cp);
     cmd.arg("dotty.tools.dotc.Main");
+    cmd.args(&args);
 
-    // Build classpath: optionally prepend classes_dir for incremental
-    let cp = if let Some(extra) = extra_cp_dir {
-        format!("{}:{}", extra.display(), resolved.user_cp)
-    } else {
-        resolved.user_cp.clone()
-    };
-    cmd.arg("-classpath").arg(&cp);
+    let output = cmd.output()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    report(config, output.status.success(), &text)
+}
 
-    cmd.arg("-d").arg(classes_dir);
-    for opt in &config.project.scalac_options {
-        cmd.arg(opt);
-    }
-    for src in sources {
-        cmd.arg(src);
+/// Print the compiler's output in the configured message format.
+fn report(config: &SbConfig, success: bool, output: &str) -> Result<()> {
+    let diags = diagnostics::parse(output);
+    match config.flags.message_format {
+        MessageFormat::Human => {
+            eprint!("{}", diagnostics::without_count_lines(output));
+            if let Some(summary) = diagnostics::summary(&diags) {
+                eprintln!("{summary}");
+            }
+        }
+        MessageFormat::Json => {
+            for d in &diags {
+                println!("{}", serde_json::to_string(d)?);
+            }
+            // Keep output we could not parse (e.g. a compiler crash) visible
+            if !success && diags.is_empty() {
+                eprint!("{output}");
+            }
+        }
     }
-
-    let status = cmd.status()?;
-    if !status.success() {
+    if !success {
         bail!("compilation failed");
     }
     Ok(())
@@ -1,10 +1,42 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::diagnostics::MessageFormat;
+
 #[derive(Debug, Deserialize)]
 pub struct SbConfig {
     pub project: Project,
+    /// Maven repositories from sb.toml, followed by those in the user-level config
+    #[serde(default)]
+    pub repositories: Vec<RepositoryConfig>,
+    /// How `sb asm` builds the fat JAR
+    #[serde(default)]
+    pub assembly: AssemblyConfig,
+    /// How `src/main/resources` is copied to the output
+    #[serde(default)]
+    pub resources: ResourcesConfig,
+    /// How `sb native` runs GraalVM's native-image
+    #[serde(default)]
+    pub native: NativeConfig,
+    /// Project roots of upstream workspace members, in build order. Set by
+    /// `Workspace::load`; empty for standalone projects.
+    #[serde(skip)]
+    pub upstream: Vec<PathBuf>,
+    /// Options given on the command line.
+    #[serde(skip)]
+    pub flags: BuildFlags,
+}
+
+/// Command-line options that change how a project is built.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuildFlags {
+    pub message_format: MessageFormat,
+    /// Fail instead of re-resolving when sb.lock is missing or out of date
+    pub locked: bool,
+    /// Use only the local Maven cache (also enabled by `SB_OFFLINE=1`)
+    pub offline: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -16,9 +48,263 @@ pub struct Project {
     #[serde(rename = "main-class")]
     pub main_class: Option<String>,
     #[serde(default)]
-    pub dependencies: Vec<String>,
+    pub dependencies: Vec<Dependency>,
     #[serde(default)]
     pub scalac_options: Vec<String>,
+    #[serde(default, rename = "test-dependencies")]
+    pub test_dependencies: Vec<Dependency>,
+    /// Names of workspace members this project depends on
+    #[serde(default, rename = "depends-on")]
+    pub depends_on: Vec<String>,
+    /// How to choose between several requested versions of a dependency
+    #[serde(default, rename = "conflict-resolution")]
+    pub conflict_resolution: ConflictResolution,
+    /// Maven groupId used by `sb publish`, e.g. "com.example"
+    #[serde(rename = "group-id")]
+    pub group_id: Option<String>,
+    /// License name for the published POM, e.g. "Apache-2.0"
+    pub license: Option<String>,
+    /// Source repository URL for the published POM
+    pub scm: Option<String>,
+}
+
+/// An entry of `dependencies` or `test-dependencies`: either a coordinate
+/// string, or an inline table such as
+/// `{ dep = "org::lib:1.0", exclude = ["com.foo:bar"], scope = "provided" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "DependencySpec")]
+pub struct Dependency {
+    /// "group:artifact:version" or "group::artifact:version"
+    pub coord: String,
+    /// "group:artifact" or "group::artifact" entries to leave out of its
+    /// transitive dependencies; the artifact may be "*"
+    pub exclude: Vec<String>,
+    pub classifier: Option<String>,
+    pub scope: DependencyScope,
+    /// Whether its own dependencies are resolved as well
+    pub transitive: bool,
+    /// Scala binary version of a "group::artifact" dependency, e.g. "2.13"
+    /// to use a Scala 2.13 library; `_3` by default
+    pub cross: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencySpec {
+    Coord(String),
+    Table(DependencyTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DependencyTable {
+    dep: String,
+    #[serde(default)]
+    exclude: Vec<String>,
+    classifier: Option<String>,
+    #[serde(default)]
+    scope: DependencyScope,
+    #[serde(default = "default_transitive")]
+    transitive: bool,
+    cross: Option<String>,
+}
+
+fn default_transitive() -> bool {
+    true
+}
+
+impl From<DependencySpec> for Dependency {
+    fn from(spec: DependencySpec) -> Self {
+        match spec {
+            DependencySpec::Coord(coord) => Self {
+                coord,
+                exclude: Vec::new(),
+                classifier: None,
+                scope: DependencyScope::Compile,
+                transitive: true,
+                cross: None,
+            },
+            DependencySpec::Table(t) => Self {
+                coord: t.dep,
+                exclude: t.exclude,
+                classifier: t.classifier,
+                scope: t.scope,
+                transitive: t.transitive,
+                cross: t.cross,
+            },
+        }
+    }
+}
+
+impl Dependency {
+    /// A stable description for hashing. Plain coordinates hash as themselves,
+    /// so locks written before inline tables existed stay fresh.
+    pub fn hash_key(&self) -> String {
+        let mut key = self.coord.clone();
+        if !self.exclude.is_empty() {
+            key.push_str(&format!(" exclude={}", self.exclude.join(",")));
+        }
+        if let Some(c) = &self.classifier {
+            key.push_str(&format!(" classifier={c}"));
+        }
+        if self.scope != DependencyScope::Compile {
+            key.push_str(&format!(" scope={}", self.scope.as_str()));
+        }
+        if !self.transitive {
+            key.push_str(" transitive=false");
+        }
+        if let Some(c) = &self.cross {
+            key.push_str(&format!(" cross={c}"));
+        }
+        key
+    }
+}
+
+/// `scope` of a dependency in sb.toml.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyScope {
+    /// On every classpath
+    #[default]
+    Compile,
+    /// Needed to compile but supplied by the runtime environment: left out of
+    /// `sb run` and `sb asm`
+    Provided,
+    /// Needed only when running: left off the compile classpath
+    Runtime,
+}
+
+impl DependencyScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Compile => "compile",
+            Self::Provided => "provided",
+            Self::Runtime => "runtime",
+        }
+    }
+}
+
+/// `conflict-resolution` in sb.toml.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictResolution {
+    /// The version closest to sb.toml in the dependency graph wins, as in Maven
+    #[default]
+    Nearest,
+    /// The highest requested version wins, as in Coursier and sbt
+    Highest,
+}
+
+/// The `[assembly]` table.
+#[derive(Debug, Default, Deserialize)]
+pub struct AssemblyConfig {
+    /// Tried in order before the built-in rules; the first whose `path` matches wins
+    #[serde(default)]
+    pub merge: Vec<MergeRule>,
+    /// Packages to relocate, e.g. "com.google.common.**" = "myapp.shaded.guava.@1"
+    #[serde(default)]
+    pub shade: BTreeMap<String, String>,
+}
+
+/// The `[resources]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourcesConfig {
+    /// Globs over paths in `src/main/resources` whose `${project.version}`
+    /// and similar placeholders are replaced when copied
+    #[serde(default)]
+    pub filter: Vec<String>,
+}
+
+/// The `[native]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NativeConfig {
+    /// Path to the native-image executable; found through `GRAALVM_HOME`,
+    /// `JAVA_HOME` or `PATH` when unset
+    #[serde(rename = "native-image")]
+    pub native_image: Option<PathBuf>,
+    /// Extra options for native-image, e.g. ["-O3", "--static"]
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// An `[[assembly.merge]]` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeRule {
+    /// Glob over entry paths: `*` within a directory, `**` across directories
+    pub path: String,
+    pub strategy: MergeStrategy,
+}
+
+/// What `sb asm` does with an entry found in more than one input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStrategy {
+    /// Join every copy, one after the other
+    Concat,
+    /// Leave the entry out
+    Discard,
+    /// Keep the first copy, in classpath order
+    First,
+    /// Keep the last copy
+    Last,
+    /// Fail unless every copy is identical
+    Error,
+    /// Keep every copy, suffixing those from dependencies with the JAR name
+    Rename,
+}
+
+impl MergeStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Concat => "concat",
+            Self::Discard => "discard",
+            Self::First => "first",
+            Self::Last => "last",
+            Self::Error => "error",
+            Self::Rename => "rename",
+        }
+    }
+}
+
+/// A `[[repositories]]` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RepositoryConfig {
+    pub name: String,
+    pub url: String,
+    /// Environment variable holding the username for basic auth
+    #[serde(rename = "username-env")]
+    pub username_env: Option<String>,
+    /// Environment variable holding the password for basic auth
+    #[serde(rename = "password-env")]
+    pub password_env: Option<String>,
+    /// Environment variable holding a bearer token
+    #[serde(rename = "token-env")]
+    pub token_env: Option<String>,
+    /// Repository names this one mirrors, e.g. "central" or "*"
+    #[serde(rename = "mirror-of")]
+    pub mirror_of: Option<String>,
+}
+
+/// `~/.config/sb/config.toml`, settings shared by all projects.
+#[derive(Debug, Default, Deserialize)]
+struct UserConfig {
+    #[serde(default)]
+    repositories: Vec<RepositoryConfig>,
+}
+
+impl UserConfig {
+    fn load() -> Result<Self> {
+        let Some(path) = dirs::config_dir().map(|d| d.join("sb").join("config.toml")) else {
+            return Ok(Self::default());
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(Self::default());
+        };
+        toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+    }
 }
 
 impl SbConfig {
@@ -26,9 +312,16 @@ impl SbConfig {
         let path = project_root.join("sb.toml");
         let content = std::fs::read_to_string(&path)
             .with_context(|| format!("failed to read {}", path.display()))?;
-        let config: SbConfig =
+        if content.lines().any(|l| l.trim() == "[workspace]") {
+            anyhow::bail!(
+                "{} is a workspace root; run this command from a member directory or use `sb build -p <member>`",
+                path.display()
+            );
+        }
+        let mut config: SbConfig =
             toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
         config.validate()?;
+        config.repositories.extend(UserConfig::load()?.repositories);
         Ok(config)
     }
 
@@ -39,6 +332,35 @@ impl SbConfig {
                 "unsupported Scala version '{sv}': only Scala 3.x is supported"
             );
         }
+        let all = self.project.dependencies.iter().chain(&self.project.test_dependencies);
+        for dep in all {
+            if let Some(cross) = &dep.cross {
+                if cross != "3" && cross != "2.13" {
+                    anyhow::bail!("invalid cross \"{cross}\" for {}: expected \"3\" or \"2.13\"", dep.coord);
+                }
+                if !dep.coord.contains("::") || dep.coord.contains(":::") {
+                    anyhow::bail!(
+                        "cross can only be used with a Scala dependency (group::artifact:version), not {}",
+                        dep.coord
+                    );
+                }
+            }
+            for excl in &dep.exclude {
+                if !excl.contains(':') {
+                    anyhow::bail!(
+                        "invalid exclusion '{excl}' for {}: expected group:artifact",
+                        dep.coord
+                    );
+                }
+            }
+        }
+        if let Some(dep) = self.project.test_dependencies.iter().find(|d| d.scope != DependencyScope::Compile) {
+            anyhow::bail!(
+                "test dependency {} cannot have scope \"{}\"; test-dependencies are only used by `sb test`",
+                dep.coord,
+                dep.scope.as_str()
+            );
+        }
         Ok(())
     }
 
@@ -46,8 +368,35 @@ impl SbConfig {
         project_root.join("src/main/scala")
     }
 
+    pub fn resource_dir(project_root: &Path) -> PathBuf {
+        project_root.join("src/main/resources")
+    }
+
+    /// Where `resource_dir` is copied to, next to the classes.
+    pub fn resource_output_dir(project_root: &Path) -> PathBuf {
+        project_root.join(".sb/resources")
+    }
+
     pub fn classes_dir(project_root: &Path) -> PathBuf {
         project_root.join(".sb/classes")
     }
 
+    pub fn test_source_dir(project_root: &Path) -> PathBuf {
+        project_root.join("src/test/scala")
+    }
+
+    pub fn test_classes_dir(project_root: &Path) -> PathBuf {
+        project_root.join(".sb/test-classes")
+    }
+
+    /// Classes and resource directories of upstream workspace members,
+    /// joined as a classpath.
+    pub fn upstream_classpath(&self) -> String {
+        self.upstream
+            .iter()
+            .flat_map(|root| [Self::classes_dir(root), Self::resource_output_dir(root)])
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(":")
+    }
 }
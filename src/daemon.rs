@@ -0,0 +1,148 @@
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::bootstrap;
+use crate::resolve;
+
+/// Last line of every compile response: "\0sb-exit <code>".
+const EXIT_MARKER: &str = "\0sb-exit ";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The daemon is used unless `SB_NO_DAEMON` is set.
+pub fn enabled() -> bool {
+    std::env::var_os("SB_NO_DAEMON").is_none()
+}
+
+fn daemon_dir() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .context("could not determine cache directory")?
+        .join("sb")
+        .join("daemon"))
+}
+
+fn socket_path(scala_version: &str) -> Result<PathBuf> {
+    Ok(daemon_dir()?.join(format!("scala-{scala_version}.sock")))
+}
+
+/// Connect to the compile daemon for `scala_version`, starting it if needed.
+/// Returns `None` if it cannot be reached, in which case the caller should
+/// fall back to spawning the compiler directly.
+pub fn connect(scala_version: &str, compiler_cp: &str) -> Option<UnixStream> {
+    let socket = socket_path(scala_version).ok()?;
+    if let Ok(stream) = UnixStream::connect(&socket) {
+        return Some(stream);
+    }
+    match start(scala_version, compiler_cp, &socket) {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            eprintln!("warning: compile daemon unavailable, using a fresh JVM: {e:#}");
+            None
+        }
+    }
+}
+
+fn start(scala_version: &str, compiler_cp: &str, socket: &Path) -> Result<UnixStream> {
+    let server_dir = bootstrap::ensure_compiled(
+        &bootstrap::COMPILE_SERVER,
+        scala_version,
+        compiler_cp,
+        compiler_cp,
+    )?;
+
+    // A leftover socket file from a daemon that died would make bind fail
+    let _ = std::fs::remove_file(socket);
+    std::fs::create_dir_all(daemon_dir()?)?;
+    let log_path = socket.with_extension("log");
+    let log = std::fs::File::create(&log_path)?;
+
+    let cp = resolve::join_classpath(&[compiler_cp, &server_dir.display().to_string()]);
+    let mut child = Command::new("java")
+        .arg("--sun-misc-unsafe-memory-access=allow")
+        .arg("-cp")
+        .arg(&cp)
+        .arg("sb.CompileServer")
+        .arg(socket)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        // Own process group, so Ctrl-C in the terminal does not kill the daemon
+        .process_group(0)
+        .spawn()
+        .context("failed to start compile daemon")?;
+    eprintln!("Started compile daemon for Scala {scala_version}");
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while Instant::now() < deadline {
+        if let Ok(stream) = UnixStream::connect(socket) {
+            return Ok(stream);
+        }
+        if let Some(status) = child.try_wait()? {
+            bail!("compile daemon exited with {status}; see {}", log_path.display());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    bail!("timed out waiting for compile daemon; see {}", log_path.display())
+}
+
+/// Send a compile request. Returns whether compilation succeeded, and the
+/// compiler's output.
+pub fn compile(mut stream: UnixStream, args: &[String]) -> Result<(bool, String)> {
+    let mut request = String::from("compile\n");
+    for arg in args {
+        if arg.contains('\n') || arg.is_empty() {
+            bail!("compiler argument not supported by the compile daemon: {arg:?}");
+        }
+        request.push_str(arg);
+        request.push('\n');
+    }
+    request.push('\n');
+    stream.write_all(request.as_bytes())?;
+
+    let mut output = String::new();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if let Some(code) = line.strip_prefix(EXIT_MARKER) {
+            return Ok((code.trim() == "0", output));
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    bail!("compile daemon closed the connection unexpectedly")
+}
+
+/// Stop every running compile daemon.
+pub fn shutdown_all() -> Result<()> {
+    let mut stopped = 0;
+    if let Ok(entries) = std::fs::read_dir(daemon_dir()?) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "sock") {
+                continue;
+            }
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let version = stem.strip_prefix("scala-").unwrap_or(&stem).to_string();
+            match UnixStream::connect(&path) {
+                Ok(mut stream) => {
+                    stream.write_all(b"shutdown\n")?;
+                    let mut response = String::new();
+                    let _ = stream.read_to_string(&mut response);
+                    eprintln!("Stopped compile daemon for Scala {version}");
+                    stopped += 1;
+                }
+                Err(_) => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+    }
+    if stopped == 0 {
+        eprintln!("No compile daemons running.");
+    }
+    Ok(())
+}
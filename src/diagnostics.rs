@@ -0,0 +1,204 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// How compiler messages are reported, chosen with `--message-format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// The compiler's own output, followed by a summary line
+    #[default]
+    Human,
+    /// One JSON object per diagnostic on stdout
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: String,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+/// Parse the output of dotc's console reporter. Each diagnostic starts with a
+/// header such as
+///
+/// ```text
+/// -- [E007] Type Mismatch Error: src/main/scala/Main.scala:3:15 ---------------
+/// ```
+///
+/// followed by the source excerpt and the message, each line prefixed with `|`.
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let output = strip_ansi(output);
+    let mut diagnostics = Vec::new();
+    let mut current: Option<(Diagnostic, Vec<&str>)> = None;
+
+    for line in output.lines() {
+        if let Some(diag) = parse_header(line) {
+            if let Some((d, body)) = current.take() {
+                diagnostics.push(finish(d, &body));
+            }
+            current = Some((diag, Vec::new()));
+        } else if let Some((_, body)) = &mut current {
+            if is_count_line(line) {
+                let (d, body) = current.take().expect("current diagnostic");
+                diagnostics.push(finish(d, &body));
+            } else {
+                body.push(line);
+            }
+        }
+    }
+    if let Some((d, body)) = current {
+        diagnostics.push(finish(d, &body));
+    }
+    diagnostics
+}
+
+/// "3 errors, 1 warning in 2 files", or `None` if there is nothing to report.
+pub fn summary(diagnostics: &[Diagnostic]) -> Option<String> {
+    let count = |severity: &str| diagnostics.iter().filter(|d| d.severity == severity).count();
+    let plural = |n: usize, word: &str| format!("{n} {word}{}", if n == 1 { "" } else { "s" });
+
+    let mut parts = Vec::new();
+    for severity in ["error", "warning"] {
+        let n = count(severity);
+        if n > 0 {
+            parts.push(plural(n, severity));
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    let files: BTreeSet<&str> = diagnostics.iter().filter_map(|d| d.file.as_deref()).collect();
+    if files.is_empty() {
+        Some(parts.join(", "))
+    } else {
+        Some(format!("{} in {}", parts.join(", "), plural(files.len(), "file")))
+    }
+}
+
+/// Compiler output with dotc's own "N errors found" trailer lines removed,
+/// since `summary` replaces them.
+pub fn without_count_lines(output: &str) -> String {
+    output
+        .lines()
+        .filter(|l| !is_count_line(&strip_ansi(l)))
+        .map(|l| format!("{l}\n"))
+        .collect()
+}
+
+fn parse_header(line: &str) -> Option<Diagnostic> {
+    let rest = line.strip_prefix("-- ")?;
+    let (code, rest) = match rest.strip_prefix('[') {
+        Some(r) => {
+            let (code, r) = r.split_once("] ")?;
+            (Some(code.to_string()), r)
+        }
+        None => (None, rest),
+    };
+    let (kind, rest) = rest.split_once(':')?;
+    let severity = severity_of(kind)?;
+
+    // What follows is "<file>:<line>:<col> ----" or just dashes
+    let location = rest.trim().trim_end_matches('-').trim_end();
+    let (file, line, column) = parse_location(location);
+    Some(Diagnostic {
+        file,
+        line,
+        column,
+        severity: severity.to_string(),
+        code,
+        message: String::new(),
+    })
+}
+
+/// The last word of the header kind, e.g. "Type Mismatch Error" -> "error".
+fn severity_of(kind: &str) -> Option<&'static str> {
+    match kind.rsplit(' ').next()? {
+        "Error" => Some("error"),
+        "Warning" => Some("warning"),
+        "Info" => Some("info"),
+        _ => None,
+    }
+}
+
+fn parse_location(location: &str) -> (Option<String>, Option<u32>, Option<u32>) {
+    if location.is_empty() {
+        return (None, None, None);
+    }
+    let mut parts = location.rsplitn(3, ':');
+    let col = parts.next().and_then(|s| s.parse().ok());
+    let line = parts.next().and_then(|s| s.parse().ok());
+    match (parts.next(), line, col) {
+        (Some(file), Some(line), Some(col)) => (Some(file.to_string()), Some(line), Some(col)),
+        _ => (Some(location.to_string()), None, None),
+    }
+}
+
+/// The message is the `|`-prefixed text after the caret line of the source excerpt.
+fn finish(mut diag: Diagnostic, body: &[&str]) -> Diagnostic {
+    let text: Vec<&str> = body
+        .iter()
+        .filter_map(|l| l.trim_start().strip_prefix('|'))
+        .collect();
+    let start = text
+        .iter()
+        .rposition(|l| {
+            let t = l.trim();
+            !t.is_empty() && t.chars().all(|c| c == '^')
+        })
+        .map_or(0, |i| i + 1);
+
+    let mut lines: Vec<&str> = text[start..]
+        .iter()
+        .copied()
+        .filter(|l| !l.trim_start().starts_with("longer explanation available"))
+        .collect();
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    diag.message = lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+    diag
+}
+
+/// dotc's trailer, e.g. "1 error found" or "2 warnings found".
+fn is_count_line(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    matches!(
+        words.as_slice(),
+        [n, "error" | "errors" | "warning" | "warnings", "found"] if n.parse::<u32>().is_ok()
+    )
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI sequence: ESC [ params final-byte
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
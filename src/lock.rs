@@ -0,0 +1,192 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::config::SbConfig;
+use crate::maven::coord::MavenCoord;
+use crate::maven::{Artifact, Pins};
+use crate::resolve;
+
+pub const LOCK_FILE: &str = "sb.lock";
+const LOCK_VERSION: u32 = 1;
+const HEADER: &str = "# This file is generated by sb. Do not edit it by hand; run `sb update` instead.\n";
+
+/// The exact dependency graph chosen for a project, stored in `sb.lock` next to `sb.toml`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    /// `resolve::dep_hash` of the sb.toml this lock was resolved from.
+    pub inputs: String,
+    #[serde(default, rename = "artifact")]
+    pub artifacts: Vec<LockedArtifact>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedArtifact {
+    pub coord: String,
+    pub scope: Scope,
+    pub repository: String,
+    pub sha256: String,
+}
+
+/// Which classpath an artifact belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Compiler,
+    Compile,
+    /// `scope = "provided"` in sb.toml: compile classpath only
+    Provided,
+    /// `scope = "runtime"` in sb.toml: run classpath only
+    Runtime,
+    Test,
+}
+
+impl Lockfile {
+    /// Read `sb.lock` from `project_root`, returning its raw content as well.
+    pub fn load(project_root: &Path) -> Result<Option<(Self, String)>> {
+        let path = project_root.join(LOCK_FILE);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let lock: Lockfile =
+            toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(Some((lock, content)))
+    }
+
+    /// Write the lock to `project_root`, returning the written content.
+    pub fn save(&self, project_root: &Path) -> Result<String> {
+        let content = format!("{HEADER}{}", toml::to_string(self)?);
+        std::fs::write(project_root.join(LOCK_FILE), &content)?;
+        Ok(content)
+    }
+
+    pub fn new(config: &SbConfig, scoped: &[(Scope, &[Artifact])]) -> Result<Self> {
+        let mut artifacts = Vec::new();
+        for (scope, list) in scoped {
+            for a in *list {
+                artifacts.push(LockedArtifact {
+                    coord: a.coord.to_string(),
+                    scope: *scope,
+                    repository: a.repository.clone(),
+                    sha256: sha256_file(&a.path)?,
+                });
+            }
+        }
+        Ok(Self {
+            version: LOCK_VERSION,
+            inputs: resolve::dep_hash(config).to_string(),
+            artifacts,
+        })
+    }
+
+    /// Whether the lock was resolved from the current dependencies in sb.toml.
+    pub fn is_fresh(&self, config: &SbConfig) -> bool {
+        self.version == LOCK_VERSION && self.inputs == resolve::dep_hash(config).to_string()
+    }
+
+    pub fn scope(&self, scope: Scope) -> impl Iterator<Item = &LockedArtifact> {
+        self.artifacts.iter().filter(move |a| a.scope == scope)
+    }
+
+    /// Locked versions of a scope as resolver pins, leaving out artifacts matched by `except`.
+    pub fn pins(&self, scope: Scope, except: Option<&str>) -> Result<Pins> {
+        let mut pins = Pins::new();
+        for a in self.scope(scope) {
+            let coord = MavenCoord::parse(&a.coord)?;
+            if except.is_some_and(|dep| matches_dep(&coord, dep)) {
+                continue;
+            }
+            pins.insert(coord.key(), coord.version);
+        }
+        Ok(pins)
+    }
+
+    /// Whether any locked artifact matches `dep`.
+    pub fn contains(&self, dep: &str) -> bool {
+        self.artifacts
+            .iter()
+            .filter_map(|a| MavenCoord::parse(&a.coord).ok())
+            .any(|c| matches_dep(&c, dep))
+    }
+
+    /// Check the JARs of a scope against their locked checksums.
+    pub fn verify(&self, scope: Scope, artifacts: &[Artifact]) -> Result<()> {
+        for artifact in artifacts {
+            let coord = artifact.coord.to_string();
+            let Some(locked) = self.scope(scope).find(|a| a.coord == coord) else {
+                continue;
+            };
+            let actual = sha256_file(&artifact.path)?;
+            if actual != locked.sha256 {
+                bail!(
+                    "checksum mismatch for {} ({}): sb.lock expects sha256 {}, found {actual}",
+                    locked.coord,
+                    artifact.path.display(),
+                    locked.sha256
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Human-readable changes from `old` to `self`, e.g. "Updating a:b 1.0 -> 1.1".
+    pub fn changes_from(&self, old: Option<&Lockfile>) -> Vec<String> {
+        let versions = |lock: &Lockfile| -> std::collections::BTreeMap<String, String> {
+            lock.artifacts
+                .iter()
+                .filter_map(|a| MavenCoord::parse(&a.coord).ok())
+                .map(|c| (format!("{}:{}", c.group_id, c.artifact_id), c.version))
+                .collect()
+        };
+        let new = versions(self);
+        let old = old.map(versions).unwrap_or_default();
+
+        let mut changes = Vec::new();
+        for (key, version) in &new {
+            match old.get(key) {
+                None => changes.push(format!("Adding {key}:{version}")),
+                Some(v) if v != version => changes.push(format!("Updating {key} {v} -> {version}")),
+                Some(_) => {}
+            }
+        }
+        for (key, version) in &old {
+            if !new.contains_key(key) {
+                changes.push(format!("Removing {key}:{version}"));
+            }
+        }
+        changes
+    }
+}
+
+/// `dep` is "group:artifact", "group::artifact" (Scala 3 artifact),
+/// "group:::artifact" (full Scala version) or a bare artifact name.
+pub fn matches_dep(coord: &MavenCoord, dep: &str) -> bool {
+    let scala_named = |name: &str| {
+        coord.artifact_id == format!("{name}_3") || coord.artifact_id == format!("{name}_2.13")
+    };
+    // e.g. "kind-projector_3.6.4"
+    let full_version_named = |name: &str| {
+        coord.artifact_id.strip_prefix(name).and_then(|s| s.strip_prefix('_')).is_some_and(|v| {
+            v.split('.').count() == 3 && v.split('.').all(|n| n.parse::<u32>().is_ok())
+        })
+    };
+    if let Some((group, name)) = dep.split_once(":::") {
+        coord.group_id == group && full_version_named(name)
+    } else if let Some((group, name)) = dep.split_once("::") {
+        coord.group_id == group && scala_named(name)
+    } else if let Some((group, artifact)) = dep.split_once(':') {
+        coord.group_id == group && coord.artifact_id == artifact
+    } else {
+        coord.artifact_id == dep || scala_named(dep)
+    }
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
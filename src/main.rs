@@ -1,15 +1,32 @@
 mod asm;
+mod bootstrap;
 mod cache;
 mod compile;
 mod config;
+#[cfg(unix)]
+mod daemon;
+mod diagnostics;
+mod lock;
 mod maven;
+mod native;
+mod package;
+mod publish;
 mod resolve;
+mod resources;
 mod run;
+mod shade;
 mod tasty;
+mod test;
+mod transaction;
+mod tree;
+mod watch;
+mod workspace;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use config::BuildFlags;
+use diagnostics::MessageFormat;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "sb", about = "Ultra-fast Scala 3 build tool", version)]
@@ -32,25 +49,142 @@ enum Commands {
         name: String,
     },
     /// Compile the project
-    Build,
+    Build {
+        /// In a workspace, build only this member and the members it depends on
+        #[arg(short, long)]
+        package: Option<String>,
+        /// How to report compiler diagnostics
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+        /// Fail if sb.lock is missing or out of date instead of updating it
+        #[arg(long)]
+        locked: bool,
+        /// Never use the network; fail if a dependency is not in the local cache
+        #[arg(long)]
+        offline: bool,
+    },
     /// Compile the project (alias for build)
     #[command(hide = true)]
-    Compile,
+    Compile {
+        #[arg(short, long)]
+        package: Option<String>,
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+        #[arg(long)]
+        locked: bool,
+        #[arg(long)]
+        offline: bool,
+    },
     /// Compile and run the main class
     Run {
+        /// Class to run instead of main-class, e.g. an @main method's name
+        #[arg(long)]
+        main: Option<String>,
         /// Arguments to pass to the program
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
+    /// Compile and run the tests in src/test/scala
+    Test {
+        /// Only run suites whose name contains one of these strings
+        filters: Vec<String>,
+    },
+    /// Rebuild on every change to the sources or sb.toml
+    Watch {
+        #[command(subcommand)]
+        action: Option<WatchAction>,
+    },
     /// Assemble a fat JAR
-    Asm,
+    Asm {
+        /// Main-Class of the JAR instead of main-class
+        #[arg(long)]
+        main: Option<String>,
+        /// List entries found in more than one input and how they were merged
+        #[arg(long)]
+        report: bool,
+    },
+    /// Build a native executable with GraalVM's native-image
+    Native {
+        /// Class to build instead of main-class
+        #[arg(long, global = true)]
+        main: Option<String>,
+        /// Build from the `sb asm` JAR instead of the runtime classpath
+        #[arg(long)]
+        assembly: bool,
+        #[command(subcommand)]
+        action: Option<NativeAction>,
+    },
+    /// Write the project's JAR, sources JAR and javadoc JAR
+    Package {
+        /// Copy the dependency JARs to .sb/lib and list them in the manifest's Class-Path
+        #[arg(long)]
+        class_path: bool,
+    },
+    /// Install the library, its sources and a POM into a local Maven repository
+    PublishLocal {
+        /// Write to ~/.m2/repository instead of sb's cache
+        #[arg(long)]
+        m2: bool,
+    },
+    /// Upload the library, its sources, a POM and checksums to a repository
+    Publish {
+        /// Name of a repository in sb.toml or ~/.config/sb/config.toml
+        #[arg(long)]
+        repo: String,
+    },
     /// Dump parsed TASTy file contents
     Tasty {
         /// Path to .tasty file
         file: PathBuf,
     },
+    /// Re-resolve dependencies and rewrite sb.lock
+    Update {
+        /// Only update this dependency (group:artifact, group::artifact, group:::artifact or artifact)
+        dep: Option<String>,
+    },
+    /// Print the dependency tree, with evicted versions and exclusions
+    Tree {
+        /// Print the dependency graph as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print every path from sb.toml to a dependency
+    Why {
+        /// The dependency (group:artifact, group::artifact or artifact)
+        dep: String,
+        /// Print the paths as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Remove build artifacts
     Clean,
+    /// Stop running compile daemons
+    Shutdown,
+}
+
+#[derive(Subcommand)]
+enum WatchAction {
+    /// Rerun the main class after each successful build
+    Run {
+        /// Arguments to pass to the program
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Rerun the tests after each change
+    Test {
+        /// Only run suites whose name contains one of these strings
+        filters: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NativeAction {
+    /// Run the main class under the tracing agent to record reflection configuration
+    Agent {
+        /// Arguments to pass to the program
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -58,11 +192,39 @@ fn main() -> Result<()> {
 
     match cli.command {
         Commands::Init { name } | Commands::New { name } => cmd_init(name),
-        Commands::Build | Commands::Compile => cmd_build(),
-        Commands::Run { args } => cmd_run(&args),
-        Commands::Asm => cmd_asm(),
+        Commands::Build {
+            package,
+            message_format,
+            locked,
+            offline,
+        }
+        | Commands::Compile {
+            package,
+            message_format,
+            locked,
+            offline,
+        } => cmd_build(
+            package,
+            BuildFlags {
+                message_format,
+                locked,
+                offline,
+            },
+        ),
+        Commands::Run { main, args } => cmd_run(main.as_deref(), &args),
+        Commands::Test { filters } => cmd_test(&filters),
+        Commands::Watch { action } => cmd_watch(action),
+        Commands::Asm { main, report } => cmd_asm(main.as_deref(), report),
+        Commands::Native { main, assembly, action } => cmd_native(main.as_deref(), assembly, action),
+        Commands::Package { class_path } => cmd_package(class_path),
+        Commands::PublishLocal { m2 } => cmd_publish_local(m2),
+        Commands::Publish { repo } => cmd_publish(&repo),
         Commands::Tasty { file } => tasty::dump_tasty(&file),
+        Commands::Update { dep } => cmd_update(dep.as_deref()),
+        Commands::Tree { json } => cmd_tree(json),
+        Commands::Why { dep, json } => cmd_why(&dep, json),
         Commands::Clean => cmd_clean(),
+        Commands::Shutdown => cmd_shutdown(),
     }
 }
 
@@ -115,33 +277,173 @@ dependencies = []
     Ok(())
 }
 
-fn cmd_build() -> Result<()> {
+/// Load the project in `root`. If it is a workspace member, the members it
+/// depends on are compiled first so their classes can go on its classpath.
+fn load_config(root: &Path) -> Result<config::SbConfig> {
+    load_config_with(root, BuildFlags::default())
+}
+
+fn load_config_with(root: &Path, flags: BuildFlags) -> Result<config::SbConfig> {
+    if let Some((mut ws, name)) = workspace::Workspace::enclosing(root)? {
+        ws.set_flags(flags);
+        let order = ws.build_order(Some(&name))?;
+        compile::compile_workspace(&order[..order.len() - 1])?;
+        return ws.into_member_config(&name);
+    }
+    let mut config = config::SbConfig::load(root)?;
+    config.flags = flags;
+    Ok(config)
+}
+
+/// Load the project in `root` without compiling upstream workspace members,
+/// for commands that only look at dependencies.
+fn load_config_unbuilt(root: &Path) -> Result<config::SbConfig> {
+    match workspace::Workspace::enclosing(root)? {
+        Some((ws, name)) => ws.into_member_config(&name),
+        None => config::SbConfig::load(root),
+    }
+}
+
+fn cmd_build(package: Option<String>, flags: BuildFlags) -> Result<()> {
     let root = project_root()?;
-    let config = config::SbConfig::load(&root)?;
+    if let Some(mut ws) = workspace::Workspace::load(&root)? {
+        ws.set_flags(flags);
+        return compile::compile_workspace(&ws.build_order(package.as_deref())?);
+    }
+    if package.is_some() {
+        anyhow::bail!("--package can only be used at the root of a workspace");
+    }
+    let config = load_config_with(&root, flags)?;
     compile::compile(&config, &root)?;
     Ok(())
 }
 
-fn cmd_run(args: &[String]) -> Result<()> {
+fn cmd_run(main: Option<&str>, args: &[String]) -> Result<()> {
     let root = project_root()?;
-    let config = config::SbConfig::load(&root)?;
-    run::run(&config, &root, args)
+    let config = load_config(&root)?;
+    run::run(&config, &root, main, args)
 }
 
-fn cmd_asm() -> Result<()> {
+fn cmd_test(filters: &[String]) -> Result<()> {
     let root = project_root()?;
-    let config = config::SbConfig::load(&root)?;
-    let jar = asm::assemble(&config, &root)?;
+    let config = load_config(&root)?;
+    test::test(&config, &root, filters)
+}
+
+fn cmd_watch(action: Option<WatchAction>) -> Result<()> {
+    let root = project_root()?;
+    let action = match action {
+        None => watch::Action::Build,
+        Some(WatchAction::Run { args }) => watch::Action::Run(args),
+        Some(WatchAction::Test { filters }) => watch::Action::Test(filters),
+    };
+    watch::watch(&root, &action, load_config)
+}
+
+fn cmd_asm(main: Option<&str>, report: bool) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config(&root)?;
+    let jar = asm::assemble(&config, &root, main, report)?;
     eprintln!("Assembly: {}", jar.display());
     Ok(())
 }
 
+fn cmd_native(main: Option<&str>, assembly: bool, action: Option<NativeAction>) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config(&root)?;
+    match action {
+        Some(NativeAction::Agent { args }) => native::agent(&config, &root, main, &args),
+        None => {
+            let executable = native::native(&config, &root, main, assembly)?;
+            eprintln!("Native image: {}", executable.display());
+            Ok(())
+        }
+    }
+}
+
+fn cmd_package(class_path: bool) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config(&root)?;
+    let packaged = package::package(&config, &root, class_path)?;
+    for jar in [&packaged.jar, &packaged.sources, &packaged.javadoc] {
+        eprintln!("Packaged: {}", jar.display());
+    }
+    Ok(())
+}
+
+fn cmd_publish_local(m2: bool) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config(&root)?;
+    let target = if m2 {
+        publish::LocalRepository::M2
+    } else {
+        publish::LocalRepository::Cache
+    };
+    publish::publish_local(&config, &root, target)
+}
+
+fn cmd_publish(repo: &str) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config(&root)?;
+    publish::publish(&config, &root, repo)
+}
+
+fn cmd_update(dep: Option<&str>) -> Result<()> {
+    let root = project_root()?;
+    if let Some(ws) = workspace::Workspace::load(&root)? {
+        let mut updated = false;
+        for member in &ws.members {
+            // With a dependency given, skip members that do not use it
+            if let Some(dep) = dep {
+                let lock = lock::Lockfile::load(&member.dir)?;
+                if !lock.is_some_and(|(lock, _)| lock.contains(dep)) {
+                    continue;
+                }
+            }
+            eprintln!("[{}]", member.name);
+            resolve::update(&member.config, &member.dir, dep)?;
+            updated = true;
+        }
+        if let (Some(dep), false) = (dep, updated) {
+            anyhow::bail!("no workspace member has a dependency matching '{dep}'");
+        }
+        return Ok(());
+    }
+    let config = load_config_unbuilt(&root)?;
+    resolve::update(&config, &root, dep)
+}
+
+fn cmd_tree(json: bool) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config_unbuilt(&root)?;
+    tree::tree(&config, &root, json)
+}
+
+fn cmd_why(dep: &str, json: bool) -> Result<()> {
+    let root = project_root()?;
+    let config = load_config_unbuilt(&root)?;
+    tree::why(&config, &root, dep, json)
+}
+
 fn cmd_clean() -> Result<()> {
     let root = project_root()?;
-    let sb_dir = root.join(".sb");
-    if sb_dir.exists() {
-        std::fs::remove_dir_all(&sb_dir)?;
-        eprintln!("Cleaned .sb/");
+    let mut dirs = vec![root.clone()];
+    if let Some(ws) = workspace::Workspace::load(&root)? {
+        dirs.extend(ws.members.into_iter().map(|m| m.dir));
     }
+    for dir in dirs {
+        let sb_dir = dir.join(".sb");
+        if sb_dir.exists() {
+            std::fs::remove_dir_all(&sb_dir)?;
+            let rel = sb_dir.strip_prefix(&root).unwrap_or(&sb_dir);
+            eprintln!("Cleaned {}/", rel.display());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_shutdown() -> Result<()> {
+    #[cfg(unix)]
+    daemon::shutdown_all()?;
     Ok(())
 }
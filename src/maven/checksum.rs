@@ -0,0 +1,47 @@
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Checksum sidecar formats published next to Maven artifacts, strongest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Sha1,
+    Md5,
+}
+
+pub const ALGORITHMS: [Algorithm; 3] = [Algorithm::Sha256, Algorithm::Sha1, Algorithm::Md5];
+
+impl Algorithm {
+    /// Extension of the sidecar file, e.g. "sha1" for `foo-1.0.jar.sha1`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Md5 => "md5",
+        }
+    }
+
+    /// Lowercase hex digest of `bytes`.
+    pub fn digest(self, bytes: &[u8]) -> String {
+        match self {
+            Algorithm::Sha256 => format!("{:x}", Sha256::digest(bytes)),
+            Algorithm::Sha1 => format!("{:x}", Sha1::digest(bytes)),
+            Algorithm::Md5 => format!("{:x}", Md5::digest(bytes)),
+        }
+    }
+}
+
+/// The hash in a sidecar file. Besides a bare hash, some repositories publish
+/// `<hash>  <filename>` (sha256sum style) or `MD5 (file) = <hash>`.
+pub fn parse_sidecar(content: &str, algorithm: Algorithm) -> Option<String> {
+    let len = match algorithm {
+        Algorithm::Sha256 => 64,
+        Algorithm::Sha1 => 40,
+        Algorithm::Md5 => 32,
+    };
+    content
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .find(|word| word.len() == len && word.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|word| word.to_ascii_lowercase())
+}
@@ -10,6 +10,10 @@ pub struct MavenCoord {
     pub group_id: String,
     pub artifact_id: String,
     pub version: String,
+    /// e.g. "natives-linux" for `lwjgl-3.3.3-natives-linux.jar`
+    pub classifier: Option<String>,
+    /// File extension of the artifact, "jar" unless a POM says otherwise
+    pub packaging: String,
 }
 
 impl MavenCoord {
@@ -18,22 +22,40 @@ impl MavenCoord {
             group_id: group_id.into(),
             artifact_id: artifact_id.into(),
             version: version.into(),
+            classifier: None,
+            packaging: "jar".to_string(),
         }
     }
 
-    /// Parse "groupId:artifactId:version"
+    /// Parse "groupId:artifactId:version", optionally followed by
+    /// ":classifier" and "@packaging", the form `Display` writes.
     pub fn parse(s: &str) -> Result<Self> {
-        let parts: Vec<&str> = s.split(':').collect();
-        if parts.len() != 3 {
+        let (rest, packaging) = match s.split_once('@') {
+            Some((rest, packaging)) => (rest, packaging),
+            None => (s, "jar"),
+        };
+        let parts: Vec<&str> = rest.split(':').collect();
+        if parts.len() != 3 && parts.len() != 4 {
             bail!("invalid Maven coordinate (expected group:artifact:version): {s}");
         }
         Ok(Self {
             group_id: parts[0].to_string(),
             artifact_id: parts[1].to_string(),
             version: parts[2].to_string(),
+            classifier: parts.get(3).map(|c| c.to_string()),
+            packaging: packaging.to_string(),
         })
     }
 
+    /// The same artifact with another classifier and packaging.
+    pub fn with_variant(&self, classifier: Option<&str>, packaging: &str) -> Self {
+        Self {
+            classifier: classifier.map(str::to_string),
+            packaging: packaging.to_string(),
+            ..self.clone()
+        }
+    }
+
     /// e.g. "org/typelevel/cats-core_3/2.12.0"
     pub fn repo_path(&self) -> String {
         format!(
@@ -48,12 +70,22 @@ impl MavenCoord {
         format!("{}-{}.{}", self.artifact_id, self.version, ext)
     }
 
-    pub fn pom_url(&self) -> String {
-        format!("{}/{}/{}", MAVEN_CENTRAL, self.repo_path(), self.filename("pom"))
+    /// e.g. "lwjgl-3.3.3-natives-linux.jar"
+    pub fn artifact_filename(&self) -> String {
+        match &self.classifier {
+            Some(c) => format!("{}-{}-{c}.{}", self.artifact_id, self.version, self.packaging),
+            None => self.filename(&self.packaging),
+        }
     }
 
-    pub fn jar_url(&self) -> String {
-        format!("{}/{}/{}", MAVEN_CENTRAL, self.repo_path(), self.filename("jar"))
+    /// Path of the POM relative to a repository root
+    pub fn pom_path(&self) -> String {
+        format!("{}/{}", self.repo_path(), self.filename("pom"))
+    }
+
+    /// Path of the JAR (or other packaging) relative to a repository root
+    pub fn jar_path(&self) -> String {
+        format!("{}/{}", self.repo_path(), self.artifact_filename())
     }
 
     pub fn local_pom_path(&self, cache_root: &Path) -> PathBuf {
@@ -61,7 +93,7 @@ impl MavenCoord {
     }
 
     pub fn local_jar_path(&self, cache_root: &Path) -> PathBuf {
-        cache_root.join(self.repo_path()).join(self.filename("jar"))
+        cache_root.join(self.repo_path()).join(self.artifact_filename())
     }
 
     /// Key for deduplication: (groupId, artifactId)
@@ -72,6 +104,13 @@ impl MavenCoord {
 
 impl fmt::Display for MavenCoord {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}:{}", self.group_id, self.artifact_id, self.version)
+        write!(f, "{}:{}:{}", self.group_id, self.artifact_id, self.version)?;
+        if let Some(c) = &self.classifier {
+            write!(f, ":{c}")?;
+        }
+        if self.packaging != "jar" {
+            write!(f, "@{}", self.packaging)?;
+        }
+        Ok(())
     }
 }
@@ -1,13 +1,18 @@
 use std::fs;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{bail, Context, Result};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
-use super::coord::MavenCoord;
+use super::checksum;
+use super::metadata;
+use super::coord::{MavenCoord, MAVEN_CENTRAL};
+use super::repository::{MissingCredentials, Repository};
+use super::Artifact;
 
 fn spinner_style() -> ProgressStyle {
     ProgressStyle::with_template("  {spinner:.cyan} {msg}")
@@ -77,27 +82,99 @@ impl ProgressTracker {
         pb.finish_with_message(format!("✓ {label}"));
         st.completed.push(pb.clone());
     }
+
+    /// Print a warning above the progress bars.
+    pub fn warn(&self, msg: &str) {
+        let _ = self.mp.println(format!("warning: {msg}"));
+    }
+}
+
+/// Returned in offline mode for a file that is not in the local cache.
+/// The fetcher also records it, so all of them can be reported at once.
+#[derive(Debug)]
+pub struct NotCached;
+
+impl std::fmt::Display for NotCached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "not in the local cache (offline mode)")
+    }
 }
 
+impl std::error::Error for NotCached {}
+
+/// Returned when no repository has a file, as opposed to one failing to serve it.
+#[derive(Debug)]
+pub struct NotFound {
+    pub file: String,
+    pub coord: String,
+    /// Names of the repositories tried
+    pub tried: Vec<String>,
+}
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} for {} not found in any repository (tried {})", self.file, self.coord, self.tried.join(", "))
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// Returned in offline mode when anything was missing from the local cache.
+#[derive(Debug)]
+pub struct MissingArtifacts(pub Vec<String>);
+
+impl std::fmt::Display for MissingArtifacts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "offline mode: {} file(s) are not in the local Maven cache:", self.0.len())?;
+        for missing in &self.0 {
+            writeln!(f, "  {missing}")?;
+        }
+        write!(f, "build once without offline mode to download them")
+    }
+}
+
+impl std::error::Error for MissingArtifacts {}
+
 pub struct MavenFetcher {
     cache_root: PathBuf,
     agent: ureq::Agent,
     tracker: Arc<ProgressTracker>,
+    /// Tried in order for every artifact
+    repositories: Vec<Repository>,
+    /// Serve only what is already cached, never touching the network
+    offline: bool,
+    /// Files requested in offline mode that were not cached, e.g. "a:b:1.0 (jar)"
+    missing: Mutex<Vec<String>>,
 }
 
 impl MavenFetcher {
-    pub fn new(tracker: Arc<ProgressTracker>) -> Result<Self> {
-        let cache_root = dirs::cache_dir()
-            .context("could not determine cache directory")?
-            .join("sb")
-            .join("maven");
+    pub fn new(tracker: Arc<ProgressTracker>, repositories: Vec<Repository>, offline: bool) -> Result<Self> {
+        let cache_root = cache_root()?;
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .new_agent();
         Ok(Self {
             cache_root,
-            agent: ureq::Agent::new_with_defaults(),
+            agent,
             tracker,
+            repositories,
+            offline,
+            missing: Mutex::new(Vec::new()),
         })
     }
 
+    /// In offline mode, fail with every file that was missing from the cache.
+    pub fn check_missing(&self) -> Result<()> {
+        let mut missing = self.missing.lock().unwrap().clone();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        missing.sort();
+        missing.dedup();
+        Err(MissingArtifacts(missing).into())
+    }
+
     /// Fetch POM XML content. Shows a per-artifact progress bar.
     pub fn fetch_pom(&self, coord: &MavenCoord) -> Result<String> {
         let label = format!("{}-{}.pom", coord.artifact_id, coord.version);
@@ -112,51 +189,183 @@ impl MavenFetcher {
 
         let pb = self.tracker.add_spinner(&label);
 
-        let url = coord.pom_url();
-        let bytes = self.http_get_with_progress(&url, &pb)
-            .with_context(|| format!("failed to fetch POM for {coord}"))?;
+        let (bytes, _) = self.download(coord, &coord.pom_path(), &pb)?;
         let body = String::from_utf8(bytes)
             .with_context(|| format!("POM for {coord} is not valid UTF-8"))?;
 
-        if let Some(parent) = local.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        fs::write(&local, &body)?;
+        write_atomic(&local, body.as_bytes())?;
 
         self.tracker.mark_done(&pb, &label);
         Ok(body)
     }
 
     /// Download JAR to cache. Shows a per-artifact progress bar.
-    pub fn fetch_jar(&self, coord: &MavenCoord) -> Result<PathBuf> {
-        let label = format!("{}-{}.jar", coord.artifact_id, coord.version);
+    /// The URL of the repository that served it is kept next to the JAR.
+    pub fn fetch_jar(&self, coord: &MavenCoord) -> Result<Artifact> {
+        let label = coord.artifact_filename();
         let local = coord.local_jar_path(&self.cache_root);
+        let origin = local.with_extension(format!("{}.origin", coord.packaging));
 
         if local.exists() {
             let pb = self.tracker.add_spinner(&label);
             self.tracker.mark_done(&pb, &label);
-            return Ok(local);
+            // JARs cached before origins were recorded could only have come from Central
+            let repository = fs::read_to_string(&origin)
+                .map(|s| s.trim().to_string())
+                .unwrap_or_else(|_| MAVEN_CENTRAL.to_string());
+            return Ok(Artifact { coord: coord.clone(), path: local, repository });
         }
 
         let pb = self.tracker.add_spinner(&label);
 
-        let url = coord.jar_url();
-        let bytes = self.http_get_with_progress(&url, &pb)
-            .with_context(|| format!("failed to fetch JAR for {coord}"))?;
+        let (bytes, repo) = self.download(coord, &coord.jar_path(), &pb)?;
+        write_atomic(&origin, repo.url.as_bytes())?;
+        write_atomic(&local, &bytes)?;
 
-        if let Some(parent) = local.parent() {
-            fs::create_dir_all(parent)?;
+        self.tracker.mark_done(&pb, &label);
+        Ok(Artifact {
+            coord: coord.clone(),
+            path: local,
+            repository: repo.url.clone(),
+        })
+    }
+
+    /// All published versions of `group:artifact`, merged from the
+    /// `maven-metadata.xml` of every repository. Each repository's copy is
+    /// cached as `maven-metadata-<name>.xml`, which is all offline mode reads.
+    pub fn fetch_versions(&self, group_id: &str, artifact_id: &str) -> Result<Vec<String>> {
+        let module = format!("{group_id}:{artifact_id}");
+        let dir = format!("{}/{artifact_id}", group_id.replace('.', "/"));
+        let cached = |repo: &Repository| {
+            self.cache_root.join(&dir).join(format!("maven-metadata-{}.xml", repo.name))
+        };
+
+        let mut versions = Vec::new();
+        if self.offline {
+            for repo in &self.repositories {
+                if let Ok(xml) = fs::read_to_string(cached(repo)) {
+                    versions.extend(metadata::parse_versions(&xml)?);
+                }
+            }
+            if versions.is_empty() {
+                self.missing.lock().unwrap().push(format!("{module} (maven-metadata.xml)"));
+                return Err(NotCached.into());
+            }
+        } else {
+            let label = format!("{artifact_id} maven-metadata.xml");
+            let pb = self.tracker.add_spinner(&label);
+            let mut errors = Vec::new();
+            // Metadata is rewritten on every release, so its checksums are not verified
+            for repo in &self.repositories {
+                let url = format!("{}/{dir}/maven-metadata.xml", repo.url);
+                match self.http_get_optional(repo, &url) {
+                    Ok(Some(xml)) => {
+                        versions.extend(metadata::parse_versions(&xml)?);
+                        write_atomic(&cached(repo), xml.as_bytes())?;
+                    }
+                    Ok(None) => {}
+                    Err(e) if e.downcast_ref::<MissingCredentials>().is_some() => return Err(e),
+                    Err(e) => errors.push(format!("{}: {e:#}", repo.name)),
+                }
+            }
+            if versions.is_empty() {
+                pb.finish_and_clear();
+                if errors.is_empty() {
+                    bail!("no versions of {module} found in any repository");
+                }
+                bail!("failed to download maven-metadata.xml for {module}: {}", errors.join("; "));
+            }
+            self.tracker.mark_done(&pb, &label);
         }
-        fs::write(&local, &bytes)?;
+        versions.sort();
+        versions.dedup();
+        Ok(versions)
+    }
 
-        self.tracker.mark_done(&pb, &label);
-        Ok(local)
+    /// Download `path` from the first repository that has it, checking its checksum.
+    fn download(&self, coord: &MavenCoord, path: &str, pb: &ProgressBar) -> Result<(Vec<u8>, &Repository)> {
+        let file = path.rsplit('/').next().unwrap_or(path);
+        if self.offline {
+            pb.finish_and_clear();
+            let kind = file.rsplit('.').next().unwrap_or(file);
+            self.missing.lock().unwrap().push(format!("{coord} ({kind})"));
+            return Err(NotCached.into());
+        }
+        let mut errors = Vec::new();
+        for repo in &self.repositories {
+            let url = format!("{}/{path}", repo.url);
+            match self.http_get_with_progress(repo, &url, pb) {
+                Ok(Some(bytes)) => {
+                    self.verify_checksum(repo, coord, &url, &bytes)?;
+                    return Ok((bytes, repo));
+                }
+                Ok(None) => {}
+                // Not a problem with the repository, so trying the next one would hide it
+                Err(e) if e.downcast_ref::<MissingCredentials>().is_some() => return Err(e),
+                Err(e) => errors.push(format!("{}: {e:#}", repo.name)),
+            }
+        }
+        if errors.is_empty() {
+            return Err(NotFound {
+                file: file.to_string(),
+                coord: coord.to_string(),
+                tried: self.repositories.iter().map(|r| r.name.clone()).collect(),
+            }
+            .into());
+        }
+        bail!("failed to download {file} for {coord}: {}", errors.join("; "))
+    }
+
+    /// Check downloaded bytes against the strongest checksum sidecar the
+    /// repository publishes for `url`.
+    fn verify_checksum(&self, repo: &Repository, coord: &MavenCoord, url: &str, bytes: &[u8]) -> Result<()> {
+        let file = url.rsplit('/').next().unwrap_or(url);
+        for algorithm in checksum::ALGORITHMS {
+            let sidecar_url = format!("{url}.{}", algorithm.extension());
+            let Some(content) = self.http_get_optional(repo, &sidecar_url)? else {
+                continue;
+            };
+            let Some(expected) = checksum::parse_sidecar(&content, algorithm) else {
+                bail!("malformed checksum file for {coord}: {sidecar_url}");
+            };
+            let actual = algorithm.digest(bytes);
+            if actual != expected {
+                bail!(
+                    "checksum mismatch for {coord} ({file}): expected {} {expected}, got {actual}",
+                    algorithm.extension()
+                );
+            }
+            return Ok(());
+        }
+        self.tracker.warn(&format!("no checksum published for {coord} ({file}); not verified"));
+        Ok(())
+    }
+
+    fn get(&self, repo: &Repository, url: &str) -> Result<ureq::http::Response<ureq::Body>> {
+        let mut request = self.agent.get(url);
+        if let Some(auth) = repo.authorization()? {
+            request = request.header("Authorization", auth);
+        }
+        request.call().map_err(|e| anyhow::anyhow!("HTTP GET {url} failed: {e}"))
+    }
+
+    /// GET a small text file, or `None` if the server does not have it.
+    fn http_get_optional(&self, repo: &Repository, url: &str) -> Result<Option<String>> {
+        let mut response = self.get(repo, url)?;
+        match response.status().as_u16() {
+            200 => Ok(Some(response.body_mut().read_to_string()?)),
+            404 => Ok(None),
+            status => bail!("HTTP {status} for {url}"),
+        }
     }
 
-    fn http_get_with_progress(&self, url: &str, pb: &ProgressBar) -> Result<Vec<u8>> {
-        let response = self.agent.get(url).call()
-            .map_err(|e| anyhow::anyhow!("HTTP GET {url} failed: {e}"))?;
+    /// Download `url`, or `None` if the server does not have it.
+    fn http_get_with_progress(&self, repo: &Repository, url: &str, pb: &ProgressBar) -> Result<Option<Vec<u8>>> {
+        let response = self.get(repo, url)?;
         let status = response.status();
+        if status == 404 {
+            return Ok(None);
+        }
         if status != 200 {
             bail!("HTTP {status} for {url}");
         }
@@ -183,6 +392,34 @@ impl MavenFetcher {
             pb.set_position(body.len() as u64);
         }
 
-        Ok(body)
+        Ok(Some(body))
+    }
+}
+
+/// Where downloaded POMs and JARs are kept, in the Maven repository layout.
+pub fn cache_root() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .context("could not determine cache directory")?
+        .join("sb")
+        .join("maven"))
+}
+
+/// Write through a temporary file in the same directory and rename it into
+/// place, so other processes never see a partially written artifact.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let parent = path.parent().context("artifact path has no parent directory")?;
+    fs::create_dir_all(parent)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = parent.join(format!(
+        ".{name}.{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp, bytes)?;
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("failed to write {}", path.display()));
     }
+    Ok(())
 }
@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+
+use super::version::ComparableVersion;
+
+/// Versions listed in an artifact's `maven-metadata.xml`, under
+/// `<versioning><versions>`.
+pub fn parse_versions(xml: &str) -> Result<Vec<String>> {
+    let doc = roxmltree::Document::parse(xml).context("failed to parse maven-metadata.xml")?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("version"))
+        .filter(|n| n.parent_element().is_some_and(|p| p.has_tag_name("versions")))
+        .filter_map(|n| n.text())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect())
+}
+
+/// An artifact-level `maven-metadata.xml` listing `versions`, as written
+/// next to the version directories by `sb publish`.
+pub fn render(group_id: &str, artifact_id: &str, versions: &[String]) -> String {
+    let highest = |release_only: bool| {
+        versions
+            .iter()
+            .filter(|v| !release_only || !v.to_ascii_uppercase().ends_with("SNAPSHOT"))
+            .max_by_key(|v| ComparableVersion::parse(v))
+    };
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metadata>\n  <groupId>{group_id}</groupId>\n  <artifactId>{artifact_id}</artifactId>\n  <versioning>\n"
+    );
+    if let Some(latest) = highest(false) {
+        xml.push_str(&format!("    <latest>{latest}</latest>\n"));
+    }
+    if let Some(release) = highest(true) {
+        xml.push_str(&format!("    <release>{release}</release>\n"));
+    }
+    xml.push_str("    <versions>\n");
+    for v in versions {
+        xml.push_str(&format!("      <version>{v}</version>\n"));
+    }
+    xml.push_str("    </versions>\n  </versioning>\n</metadata>\n");
+    xml
+}
@@ -1,22 +1,73 @@
+pub mod checksum;
 pub mod coord;
 pub mod fetch;
+pub mod metadata;
 pub mod pom;
+pub mod repository;
 pub mod resolve;
+pub mod version;
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use anyhow::Result;
 
 use self::coord::MavenCoord;
 use self::fetch::{MavenFetcher, ProgressTracker};
+use self::repository::Repository;
+use self::resolve::{Resolution, Root};
+use crate::config::ConflictResolution;
 
-/// Resolve Maven coordinates and return a colon-separated classpath of local JAR paths.
-pub fn resolve_classpath(deps: &[String], tracker: &Arc<ProgressTracker>) -> Result<String> {
-    let coords: Vec<MavenCoord> = deps
-        .iter()
-        .map(|s| MavenCoord::parse(s))
-        .collect::<Result<_>>()?;
+/// A resolved dependency and its JAR in the local cache.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub coord: MavenCoord,
+    pub path: PathBuf,
+    /// Base URL of the repository the JAR was downloaded from
+    pub repository: String,
+}
+
+/// Versions to use for transitive dependencies instead of the ones their POMs ask for,
+/// keyed by (groupId, artifactId).
+pub type Pins = HashMap<(String, String), String>;
+
+/// Resolve dependencies transitively and download their JARs, sorted by coordinate.
+/// Also returns the resolution itself, with the graph and the versions that lost a conflict.
+pub fn resolve_artifacts(
+    roots: &[Root],
+    pins: &Pins,
+    strategy: ConflictResolution,
+    repositories: &[Repository],
+    offline: bool,
+    tracker: &Arc<ProgressTracker>,
+) -> Result<(Vec<Artifact>, Resolution)> {
+    let fetcher = MavenFetcher::new(Arc::clone(tracker), repositories.to_vec(), offline)?;
+    resolve::resolve_and_fetch(&fetcher, roots, pins, strategy)
+}
+
+/// Resolve dependencies transitively without downloading any JARs.
+pub fn resolve_graph(
+    roots: &[Root],
+    pins: &Pins,
+    strategy: ConflictResolution,
+    repositories: &[Repository],
+    offline: bool,
+    tracker: &Arc<ProgressTracker>,
+) -> Result<Resolution> {
+    let fetcher = MavenFetcher::new(Arc::clone(tracker), repositories.to_vec(), offline)?;
+    let resolution = resolve::resolve(&fetcher, roots, pins, strategy)?;
+    fetcher.check_missing()?;
+    Ok(resolution)
+}
 
-    let fetcher = MavenFetcher::new(Arc::clone(tracker))?;
-    resolve::resolve_and_fetch(&fetcher, &coords)
+/// Download the JARs of already resolved coordinates, without reading any POMs.
+pub fn fetch_artifacts(
+    coords: &[MavenCoord],
+    repositories: &[Repository],
+    offline: bool,
+    tracker: &Arc<ProgressTracker>,
+) -> Result<Vec<Artifact>> {
+    let fetcher = MavenFetcher::new(Arc::clone(tracker), repositories.to_vec(), offline)?;
+    resolve::fetch_all(&fetcher, coords, false)
 }
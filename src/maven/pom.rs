@@ -12,8 +12,8 @@ pub struct PomDep {
     pub version: Option<String>,
     pub scope: String,
     pub optional: bool,
-    #[allow(dead_code)]
     pub dep_type: String,
+    pub classifier: Option<String>,
     pub exclusions: Vec<(String, String)>,
 }
 
@@ -144,6 +144,7 @@ fn parse_dep(node: &roxmltree::Node) -> Option<PomDep> {
         scope: child_text(node, "scope").unwrap_or_else(|| "compile".to_string()),
         optional: child_text(node, "optional").map(|s| s == "true").unwrap_or(false),
         dep_type: child_text(node, "type").unwrap_or_else(|| "jar".to_string()),
+        classifier: child_text(node, "classifier"),
         exclusions: parse_exclusions(node),
     })
 }
@@ -263,5 +264,8 @@ fn interpolate_pom(pom: &mut Pom, props: &HashMap<String, String>) {
             *v = interpolate(v, props);
         }
         d.scope = interpolate(&d.scope, props);
+        if let Some(ref mut c) = d.classifier {
+            *c = interpolate(c, props);
+        }
     }
 }
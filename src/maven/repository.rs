@@ -0,0 +1,156 @@
+use anyhow::Result;
+use base64::Engine;
+
+use super::coord::MAVEN_CENTRAL;
+use crate::config::RepositoryConfig;
+
+/// The environment variables holding a repository's credentials. They are
+/// read only when a request goes to the repository, so that one which is
+/// never contacted does not need them.
+#[derive(Debug, Clone)]
+enum Auth {
+    Basic { username_env: String, password_env: Option<String> },
+    Bearer(String),
+}
+
+/// A remote Maven repository with its credentials read from the environment.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub name: String,
+    /// Base URL without a trailing slash
+    pub url: String,
+    auth: Option<Auth>,
+}
+
+/// A request needs a credential whose environment variable is not set.
+#[derive(Debug)]
+pub struct MissingCredentials {
+    pub repository: String,
+    pub var: String,
+}
+
+impl std::fmt::Display for MissingCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "repository '{}': environment variable {} is not set", self.repository, self.var)
+    }
+}
+
+impl std::error::Error for MissingCredentials {}
+
+impl Repository {
+    pub fn central() -> Self {
+        Self {
+            name: "central".to_string(),
+            url: MAVEN_CENTRAL.to_string(),
+            auth: None,
+        }
+    }
+
+    fn from_config(config: &RepositoryConfig) -> Self {
+        let auth = if let Some(var) = &config.token_env {
+            Some(Auth::Bearer(var.clone()))
+        } else {
+            config.username_env.as_ref().map(|var| Auth::Basic {
+                username_env: var.clone(),
+                password_env: config.password_env.clone(),
+            })
+        };
+        Self {
+            name: config.name.clone(),
+            url: config.url.trim_end_matches('/').to_string(),
+            auth,
+        }
+    }
+
+    /// Value of the `Authorization` header, if the repository needs credentials.
+    pub fn authorization(&self) -> Result<Option<String>> {
+        let env = |var: &str| {
+            std::env::var(var).map_err(|_| MissingCredentials {
+                repository: self.name.clone(),
+                var: var.to_string(),
+            })
+        };
+        Ok(match &self.auth {
+            Some(Auth::Basic { username_env, password_env }) => {
+                let username = env(username_env)?;
+                let password = match password_env {
+                    Some(var) => env(var)?,
+                    None => String::new(),
+                };
+                let encoded = base64::engine::general_purpose::STANDARD
+                    .encode(format!("{username}:{password}"));
+                Some(format!("Basic {encoded}"))
+            }
+            Some(Auth::Bearer(var)) => Some(format!("Bearer {}", env(var)?)),
+            None => None,
+        })
+    }
+}
+
+/// The configured repository called `name`, e.g. for `sb publish --repo <name>`.
+pub fn named(configs: &[RepositoryConfig], name: &str) -> Result<Repository> {
+    match configs.iter().find(|c| c.name == name) {
+        Some(config) => {
+            // Publishing always contacts it, so missing credentials fail up front
+            let repo = Repository::from_config(config);
+            repo.authorization()?;
+            Ok(repo)
+        }
+        None => anyhow::bail!(
+            "no repository named '{name}' in sb.toml or ~/.config/sb/config.toml"
+        ),
+    }
+}
+
+/// The repositories to try, in order: those configured (project first, then
+/// user-level) followed by Maven Central unless one is already named "central".
+/// A repository with `mirror-of` takes the place of every repository it mirrors.
+pub fn effective(configs: &[RepositoryConfig]) -> Vec<Repository> {
+    let (mirrors, plain): (Vec<&RepositoryConfig>, Vec<&RepositoryConfig>) =
+        configs.iter().partition(|c| c.mirror_of.is_some());
+
+    // Repositories by name, `None` standing for Maven Central
+    let mut declared: Vec<(&str, Option<&RepositoryConfig>)> = Vec::new();
+    for config in plain {
+        if !declared.iter().any(|(name, _)| *name == config.name) {
+            declared.push((&config.name, Some(config)));
+        }
+    }
+    if !declared.iter().any(|(name, _)| *name == "central") {
+        declared.push(("central", None));
+    }
+
+    let mut result: Vec<Repository> = Vec::new();
+    for (name, config) in declared {
+        let mirror = mirrors.iter().find(|m| {
+            m.mirror_of
+                .as_deref()
+                .is_some_and(|pattern| mirror_matches(pattern, name))
+        });
+        let repo = match (mirror, config) {
+            (Some(m), _) => Repository::from_config(m),
+            (None, Some(config)) => Repository::from_config(config),
+            (None, None) => Repository::central(),
+        };
+        if !result.iter().any(|r| r.name == repo.name) {
+            result.push(repo);
+        }
+    }
+    result
+}
+
+/// Maven's mirrorOf syntax: a comma-separated list of repository names, where
+/// `*` matches any repository and `!name` excludes one.
+fn mirror_matches(pattern: &str, name: &str) -> bool {
+    let mut matched = false;
+    for part in pattern.split(',').map(str::trim) {
+        if let Some(excluded) = part.strip_prefix('!') {
+            if excluded == name {
+                return false;
+            }
+        } else if part == "*" || part == name {
+            matched = true;
+        }
+    }
+    matched
+}
@@ -1,58 +1,308 @@
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::path::PathBuf;
-
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use anyhow::{bail, Result};
+use serde::Serialize;
 
 use super::coord::MavenCoord;
-use super::fetch::MavenFetcher;
+use super::fetch::{MavenFetcher, NotCached, NotFound};
 use super::pom::{self, ManagedDep, Pom};
+use super::version::{self, ComparableVersion, VersionRange};
+use super::{Artifact, Pins};
+use crate::config::ConflictResolution;
+
+/// Who asked for a root dependency; edges declared by sb.toml come from here.
+pub const ROOT_REQUESTER: &str = "sb.toml";
+
+/// A dependency declared in sb.toml.
+#[derive(Debug, Clone)]
+pub struct Root {
+    pub coord: MavenCoord,
+    /// (groupId, artifactId) pairs left out of its transitive dependencies;
+    /// the artifact may be "*", and ("*", "*") excludes everything
+    pub exclusions: Vec<(String, String)>,
+    /// Whether its own dependencies are resolved as well
+    pub transitive: bool,
+    /// Shown as the scope of its edge in the graph, e.g. "provided"
+    pub scope: String,
+}
+
+impl Root {
+    pub fn new(coord: MavenCoord) -> Self {
+        Self {
+            coord,
+            exclusions: Vec::new(),
+            transitive: true,
+            scope: "compile".to_string(),
+        }
+    }
+}
 
 struct QueueEntry {
     coord: MavenCoord,
     depth: u32,
     exclusions: HashSet<(String, String)>,
+    transitive: bool,
+}
+
+/// (classifier, packaging) of an artifact file.
+type Variant = (Option<String>, String);
+
+/// A version of a module that lost to another one during resolution.
+#[derive(Debug, Clone, Serialize)]
+pub struct Eviction {
+    /// "group:artifact"
+    pub module: String,
+    pub selected: String,
+    pub evicted: String,
+    /// Coordinates whose POMs asked for the evicted version, or "sb.toml"
+    pub requested_by: Vec<String>,
+}
+
+/// A dependency declared by sb.toml or by the POM of a resolved artifact.
+#[derive(Debug, Clone, Serialize)]
+pub struct Edge {
+    /// Coordinate of the declaring artifact, or "sb.toml"
+    pub from: String,
+    /// "group:artifact"
+    pub module: String,
+    /// Empty when an excluded dependency declares no version
+    pub requested: String,
+    /// e.g. "natives-linux"
+    pub classifier: Option<String>,
+    /// The version that was resolved; `None` when excluded
+    pub selected: Option<String>,
+    /// Maven scope of the declaration, "compile" or "runtime"
+    pub scope: String,
+    /// Left out because of an `<exclusion>` on the path to it
+    pub excluded: bool,
+}
+
+/// The outcome of resolving a set of roots.
+#[derive(Default)]
+pub struct Resolution {
+    pub coords: Vec<MavenCoord>,
+    pub evictions: Vec<Eviction>,
+    /// The dependency graph, in the order the declarations were visited
+    pub edges: Vec<Edge>,
+}
+
+/// Versions chosen for each (module, range) pair, shared between walks.
+type RangeCache = HashMap<((String, String), String), String>;
+
+/// One breadth-first pass over the dependency graph.
+struct Walk {
+    resolved: HashMap<(String, String), MavenCoord>,
+    /// Every version asked for per module, with the coordinates that asked for it
+    requests: HashMap<(String, String), Vec<(String, String)>>,
+    edges: Vec<Edge>,
+    /// The files requested of each module; just its main JAR when absent
+    variants: HashMap<(String, String), BTreeSet<Variant>>,
+    pom_only: HashSet<(String, String)>,
+    warnings: Vec<String>,
 }
 
 /// Resolve transitive dependencies for a list of root Maven coordinates.
 /// Returns de-duplicated list of coordinates (compile+runtime scope only).
-pub fn resolve(fetcher: &MavenFetcher, roots: &[MavenCoord]) -> Result<Vec<MavenCoord>> {
-    let mut resolved: HashMap<(String, String), MavenCoord> = HashMap::new();
-    let mut dep_mgmt: HashMap<(String, String), ManagedDep> = HashMap::new();
+/// Transitive dependencies listed in `pins` get the pinned version.
+///
+/// When several versions of a module are requested, `strategy` picks one:
+/// `Nearest` keeps the one closest to the roots (the first found at that
+/// depth, like Maven), `Highest` keeps the newest (like Coursier).
+pub fn resolve(
+    fetcher: &MavenFetcher,
+    roots: &[Root],
+    pins: &Pins,
+    strategy: ConflictResolution,
+) -> Result<Resolution> {
     let mut pom_cache: HashMap<MavenCoord, Pom> = HashMap::new();
+    let mut ranges = RangeCache::new();
+    let mut raised = Pins::new();
+    let mut walk = traverse(fetcher, roots, pins, &raised, &mut pom_cache, &mut ranges)?;
+
+    if strategy == ConflictResolution::Highest {
+        // Raising a module can change what it depends on, so walk again until
+        // every module is at the highest version requested. Versions only go
+        // up, so this terminates.
+        loop {
+            let mut changed = false;
+            for (key, requests) in &walk.requests {
+                if pins.contains_key(key) {
+                    continue;
+                }
+                let Some(highest) = requests
+                    .iter()
+                    .map(|(v, _)| v)
+                    .max_by_key(|v| ComparableVersion::parse(v))
+                else {
+                    continue;
+                };
+                let current = walk.resolved.get(key).map(|c| &c.version);
+                if current.is_some_and(|c| ComparableVersion::parse(c) < ComparableVersion::parse(highest))
+                    && raised.get(key).is_none_or(|r| ComparableVersion::parse(r) < ComparableVersion::parse(highest))
+                {
+                    raised.insert(key.clone(), highest.clone());
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+            walk = traverse(fetcher, roots, pins, &raised, &mut pom_cache, &mut ranges)?;
+        }
+    }
+
+    for warning in &walk.warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    let mut evictions = Vec::new();
+    for (key, requests) in &walk.requests {
+        let Some(selected) = walk.resolved.get(key) else {
+            continue;
+        };
+        let mut by_version: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (version, requester) in requests {
+            if *version != selected.version {
+                by_version.entry(version).or_default().push(requester.clone());
+            }
+        }
+        for (version, mut requested_by) in by_version {
+            requested_by.sort();
+            requested_by.dedup();
+            evictions.push(Eviction {
+                module: format!("{}:{}", key.0, key.1),
+                selected: selected.version.clone(),
+                evicted: version.to_string(),
+                requested_by,
+            });
+        }
+    }
+    evictions.sort_by(|a, b| {
+        a.module
+            .cmp(&b.module)
+            .then_with(|| ComparableVersion::parse(&a.evicted).cmp(&ComparableVersion::parse(&b.evicted)))
+    });
+
+    let mut edges = walk.edges;
+    for edge in edges.iter_mut().filter(|e| !e.excluded) {
+        edge.selected = edge
+            .module
+            .split_once(':')
+            .and_then(|(g, a)| walk.resolved.get(&(g.to_string(), a.to_string())))
+            .map(|c| c.version.clone());
+    }
+
+    let main_jar: BTreeSet<Variant> = [(None, "jar".to_string())].into();
+    let mut coords = Vec::new();
+    for (key, coord) in &walk.resolved {
+        for (classifier, packaging) in walk.variants.get(key).unwrap_or(&main_jar) {
+            // <packaging>pom</packaging> artifacts have no JAR to put on the classpath
+            let no_file = packaging == "pom"
+                || (classifier.is_none() && packaging == "jar" && walk.pom_only.contains(key));
+            if !no_file {
+                coords.push(coord.with_variant(classifier.as_deref(), packaging));
+            }
+        }
+    }
+    Ok(Resolution { coords, evictions, edges })
+}
+
+/// Breadth-first traversal keeping the first version seen of each module.
+/// `pins` apply to transitive dependencies, `raised` to roots as well.
+fn traverse(
+    fetcher: &MavenFetcher,
+    roots: &[Root],
+    pins: &Pins,
+    raised: &Pins,
+    pom_cache: &mut HashMap<MavenCoord, Pom>,
+    ranges: &mut RangeCache,
+) -> Result<Walk> {
+    let mut walk = Walk {
+        resolved: HashMap::new(),
+        requests: HashMap::new(),
+        edges: Vec::new(),
+        variants: HashMap::new(),
+        pom_only: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    let mut dep_mgmt: HashMap<(String, String), ManagedDep> = HashMap::new();
     let mut queue: VecDeque<QueueEntry> = VecDeque::new();
 
-    for coord in roots {
+    for root in roots {
+        let key = root.coord.key();
+        // The graph is built from plain coordinates; which files to fetch is kept aside
+        let mut coord = MavenCoord::new(&key.0, &key.1, &root.coord.version);
+        walk.variants
+            .entry(key.clone())
+            .or_default()
+            .insert((root.coord.classifier.clone(), root.coord.packaging.clone()));
+        if version::is_range(&coord.version) {
+            match select_in_range(fetcher, &key, &coord.version, ranges) {
+                Ok(v) => coord.version = v,
+                // Recorded by the fetcher and reported together once resolution finishes
+                Err(e) if e.downcast_ref::<NotCached>().is_some() => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        walk.requests
+            .entry(key.clone())
+            .or_default()
+            .push((coord.version.clone(), ROOT_REQUESTER.to_string()));
+        walk.edges.push(Edge {
+            from: ROOT_REQUESTER.to_string(),
+            module: format!("{}:{}", key.0, key.1),
+            requested: coord.version.clone(),
+            classifier: root.coord.classifier.clone(),
+            selected: None,
+            scope: root.scope.clone(),
+            excluded: false,
+        });
+        if let Some(v) = raised.get(&key) {
+            coord.version = v.clone();
+        }
         queue.push_back(QueueEntry {
-            coord: coord.clone(),
+            coord,
             depth: 0,
-            exclusions: HashSet::new(),
+            exclusions: root.exclusions.iter().cloned().collect(),
+            // The same module may be listed again, e.g. with another classifier
+            transitive: roots.iter().any(|r| r.coord.key() == key && r.transitive),
         });
     }
 
     while let Some(entry) = queue.pop_front() {
         let key = entry.coord.key();
 
-        if resolved.contains_key(&key) {
+        if walk.resolved.contains_key(&key) {
             continue;
         }
 
-        resolved.insert(key, entry.coord.clone());
+        walk.resolved.insert(key, entry.coord.clone());
 
-        let effective = match pom::resolve_effective_pom(fetcher, &entry.coord, &mut pom_cache) {
+        let effective = match pom::resolve_effective_pom(fetcher, &entry.coord, pom_cache) {
             Ok(p) => p,
             Err(e) => {
+                // Recorded by the fetcher and reported together once resolution finishes
+                if e.downcast_ref::<NotCached>().is_some() {
+                    continue;
+                }
                 if entry.depth > 0 {
-                    eprintln!("warning: failed to resolve POM for {}: {e}", entry.coord);
+                    walk.warnings.push(format!("failed to resolve POM for {}: {e}", entry.coord));
                     continue;
                 }
                 return Err(e);
             }
         };
 
+        if effective.packaging == "pom" {
+            walk.pom_only.insert(entry.coord.key());
+        }
+        if !entry.transitive {
+            continue;
+        }
+
         for md in &effective.dependency_management {
             if md.dep_type == "pom" && md.scope.as_deref() == Some("import") {
                 let bom_coord = MavenCoord::new(&md.group_id, &md.artifact_id, &md.version);
-                if let Ok(bom_pom) = pom::resolve_effective_pom(fetcher, &bom_coord, &mut pom_cache) {
+                if let Ok(bom_pom) = pom::resolve_effective_pom(fetcher, &bom_coord, pom_cache) {
                     for bom_md in &bom_pom.dependency_management {
                         let bom_key = (bom_md.group_id.clone(), bom_md.artifact_id.clone());
                         dep_mgmt.entry(bom_key).or_insert_with(|| bom_md.clone());
@@ -77,36 +327,75 @@ pub fn resolve(fetcher: &MavenFetcher, roots: &[MavenCoord]) -> Result<Vec<Maven
 
             if entry.exclusions.contains(&dep_key)
                 || entry.exclusions.contains(&(dep.group_id.clone(), "*".to_string()))
+                || entry.exclusions.contains(&("*".to_string(), "*".to_string()))
             {
+                walk.edges.push(Edge {
+                    from: entry.coord.to_string(),
+                    module: format!("{}:{}", dep.group_id, dep.artifact_id),
+                    requested: dep.version.clone().unwrap_or_default(),
+                    classifier: dep.classifier.clone(),
+                    selected: None,
+                    scope: scope.to_string(),
+                    excluded: true,
+                });
                 continue;
             }
 
-            if resolved.contains_key(&dep_key) {
-                continue;
-            }
-
-            let version = if let Some(ref v) = dep.version {
-                if v.is_empty() { None } else { Some(v.clone()) }
-            } else {
-                None
+            let requested = match &dep.version {
+                Some(v) if !v.is_empty() => Some(v.clone()),
+                _ => None,
             };
-            let version = version.or_else(|| {
+            let requested = requested.or_else(|| {
                 dep_mgmt.get(&dep_key).map(|md| md.version.clone())
             });
 
-            let version = match version {
-                Some(v) => v,
-                None => {
-                    eprintln!("warning: no version for {}:{}, skipping", dep.group_id, dep.artifact_id);
-                    continue;
+            let Some(requested) = requested else {
+                walk.warnings.push(format!("no version for {}:{}, skipping", dep.group_id, dep.artifact_id));
+                continue;
+            };
+
+            let requested = if version::is_range(&requested) {
+                match select_in_range(fetcher, &dep_key, &requested, ranges) {
+                    Ok(v) => v,
+                    Err(e) if e.downcast_ref::<NotCached>().is_some() => continue,
+                    Err(e) => {
+                        walk.warnings.push(format!("{e:#}, skipping"));
+                        continue;
+                    }
                 }
+            } else {
+                requested
             };
 
-            if version.starts_with('[') || version.starts_with('(') {
-                eprintln!("warning: version ranges not supported: {}:{}:{version}, skipping", dep.group_id, dep.artifact_id);
+            // Recorded even when the module is already resolved, for the eviction report
+            walk.requests
+                .entry(dep_key.clone())
+                .or_default()
+                .push((requested.clone(), entry.coord.to_string()));
+            walk.edges.push(Edge {
+                from: entry.coord.to_string(),
+                module: format!("{}:{}", dep.group_id, dep.artifact_id),
+                requested: requested.clone(),
+                classifier: dep.classifier.clone(),
+                selected: None,
+                scope: scope.to_string(),
+                excluded: false,
+            });
+            walk.variants
+                .entry(dep_key.clone())
+                .or_default()
+                .insert(variant(&dep.dep_type, dep.classifier.as_deref()));
+
+            if walk.resolved.contains_key(&dep_key) {
                 continue;
             }
 
+            let version = pins
+                .get(&dep_key)
+                .or_else(|| raised.get(&dep_key))
+                .cloned()
+                .unwrap_or(requested);
+
             let mut child_exclusions = entry.exclusions.clone();
             for excl in &dep.exclusions {
                 child_exclusions.insert(excl.clone());
@@ -122,32 +411,78 @@ pub fn resolve(fetcher: &MavenFetcher, roots: &[MavenCoord]) -> Result<Vec<Maven
                 coord: child_coord,
                 depth: entry.depth + 1,
                 exclusions: child_exclusions,
+                transitive: true,
             });
         }
     }
 
-    Ok(resolved.into_values().collect())
+    Ok(walk)
 }
 
-/// Resolve dependencies and download all JARs in parallel. Returns classpath string.
-pub fn resolve_and_fetch(fetcher: &MavenFetcher, roots: &[MavenCoord]) -> Result<String> {
-    let coords = resolve(fetcher, roots)?;
+/// The file a POM dependency of `<type>` and `<classifier>` refers to.
+fn variant(dep_type: &str, classifier: Option<&str>) -> Variant {
+    let classifier = classifier.map(str::to_string);
+    match dep_type {
+        "jar" | "bundle" | "maven-plugin" | "ejb" => (classifier, "jar".to_string()),
+        "test-jar" => (classifier.or_else(|| Some("tests".to_string())), "jar".to_string()),
+        "pom" => (None, "pom".to_string()),
+        other => (classifier, other.to_string()),
+    }
+}
+
+/// The highest released version of a module that `range` allows, read from
+/// the repositories' `maven-metadata.xml`.
+fn select_in_range(
+    fetcher: &MavenFetcher,
+    key: &(String, String),
+    range: &str,
+    ranges: &mut RangeCache,
+) -> Result<String> {
+    let cache_key = (key.clone(), range.to_string());
+    if let Some(v) = ranges.get(&cache_key) {
+        return Ok(v.clone());
+    }
+    let parsed = VersionRange::parse(range)?;
+    let versions = fetcher.fetch_versions(&key.0, &key.1)?;
+    let Some(selected) = parsed.highest_release(&versions) else {
+        bail!("no version of {}:{} matches {range}", key.0, key.1);
+    };
+    ranges.insert(cache_key, selected.clone());
+    Ok(selected.clone())
+}
 
-    // Download JARs in parallel — each thread adds its own progress bar via fetcher
-    let jar_paths: Vec<Result<PathBuf>> = std::thread::scope(|s| {
+/// Resolve dependencies and download all JARs in parallel.
+pub fn resolve_and_fetch(
+    fetcher: &MavenFetcher,
+    roots: &[Root],
+    pins: &Pins,
+    strategy: ConflictResolution,
+) -> Result<(Vec<Artifact>, Resolution)> {
+    let resolution = resolve(fetcher, roots, pins, strategy)?;
+    let artifacts = fetch_all(fetcher, &resolution.coords, true)?;
+    Ok((artifacts, resolution))
+}
+
+/// Download JARs in parallel, sorted by coordinate. With `skip_missing`,
+/// artifacts without a JAR (e.g. POM-only parents) are left out instead of failing.
+pub fn fetch_all(fetcher: &MavenFetcher, coords: &[MavenCoord], skip_missing: bool) -> Result<Vec<Artifact>> {
+    // Each thread adds its own progress bar via fetcher
+    let results: Vec<Result<Artifact>> = std::thread::scope(|s| {
         let handles: Vec<_> = coords.iter().map(|c| {
             s.spawn(|| fetcher.fetch_jar(c))
         }).collect();
         handles.into_iter().map(|h| h.join().expect("JAR download panicked")).collect()
     });
 
-    let mut paths = Vec::new();
-    for result in jar_paths {
+    let mut artifacts = Vec::new();
+    for result in results {
         match result {
-            Ok(p) => paths.push(p),
+            Ok(artifact) => artifacts.push(artifact),
             Err(e) => {
-                let msg = format!("{e}");
-                if msg.contains("HTTP 404") {
+                if e.downcast_ref::<NotCached>().is_some() {
+                    continue;
+                }
+                if skip_missing && e.downcast_ref::<NotFound>().is_some() {
                     continue;
                 }
                 bail!("{e}");
@@ -155,10 +490,7 @@ pub fn resolve_and_fetch(fetcher: &MavenFetcher, roots: &[MavenCoord]) -> Result
         }
     }
 
-    let classpath = paths.iter()
-        .map(|p| p.to_string_lossy().to_string())
-        .collect::<Vec<_>>()
-        .join(":");
-
-    Ok(classpath)
+    fetcher.check_missing()?;
+    artifacts.sort_by_key(|a| a.coord.to_string());
+    Ok(artifacts)
 }
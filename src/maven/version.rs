@@ -0,0 +1,245 @@
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+
+/// A Maven version, ordered like Maven's `ComparableVersion`: numeric parts
+/// compare as numbers and qualifiers rank as
+/// `alpha < beta < milestone < rc < snapshot < (release) < sp`.
+///
+/// So `1.10 > 1.9`, `1.0-RC1 < 1.0` and `1.0 == 1.0.0`.
+#[derive(Debug, Clone)]
+pub struct ComparableVersion {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Number(u64),
+    Qualifier(String),
+}
+
+impl ComparableVersion {
+    pub fn parse(version: &str) -> Self {
+        let mut items = Vec::new();
+        let mut current = String::new();
+        let mut digits = false;
+        for c in version.to_ascii_lowercase().chars() {
+            if c == '.' || c == '-' || c == '_' || c == '+' {
+                push_item(&mut items, &mut current, digits);
+                continue;
+            }
+            // A switch between digits and letters also separates items: "rc1" = "rc", "1"
+            if !current.is_empty() && c.is_ascii_digit() != digits {
+                push_item(&mut items, &mut current, digits);
+            }
+            digits = c.is_ascii_digit();
+            current.push(c);
+        }
+        push_item(&mut items, &mut current, digits);
+
+        // Trailing zeros and release markers do not change the version: 1.0.0 == 1
+        while items
+            .last()
+            .is_some_and(|i| *i == Item::Number(0) || qualifier_rank(i) == Some(RELEASE))
+        {
+            items.pop();
+        }
+        Self { items }
+    }
+}
+
+fn push_item(items: &mut Vec<Item>, current: &mut String, digits: bool) {
+    if current.is_empty() {
+        return;
+    }
+    let item = if digits {
+        Item::Number(current.parse().unwrap_or(u64::MAX))
+    } else {
+        Item::Qualifier(current.clone())
+    };
+    items.push(item);
+    current.clear();
+}
+
+const RELEASE: u8 = 6;
+
+/// Rank of well-known qualifiers; `None` for numbers and unknown qualifiers.
+fn qualifier_rank(item: &Item) -> Option<u8> {
+    let Item::Qualifier(q) = item else {
+        return None;
+    };
+    match q.as_str() {
+        "alpha" | "a" => Some(1),
+        "beta" | "b" => Some(2),
+        "milestone" | "m" => Some(3),
+        "rc" | "cr" => Some(4),
+        "snapshot" => Some(5),
+        "" | "ga" | "final" | "release" => Some(RELEASE),
+        "sp" => Some(7),
+        _ => None,
+    }
+}
+
+fn padding(other: &Item) -> Item {
+    match other {
+        Item::Number(_) => Item::Number(0),
+        Item::Qualifier(_) => Item::Qualifier(String::new()),
+    }
+}
+
+fn compare_items(a: Option<&Item>, b: Option<&Item>) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        // A missing item compares like 0 against a number and like a release against a qualifier
+        (Some(x), None) => compare_items(Some(x), Some(&padding(x))),
+        (None, Some(y)) => compare_items(Some(&padding(y)), Some(y)),
+        (Some(Item::Number(x)), Some(Item::Number(y))) => x.cmp(y),
+        // A number is newer than any qualifier: 1.1 > 1-rc
+        (Some(Item::Number(_)), Some(Item::Qualifier(_))) => Ordering::Greater,
+        (Some(Item::Qualifier(_)), Some(Item::Number(_))) => Ordering::Less,
+        (Some(x @ Item::Qualifier(qx)), Some(y @ Item::Qualifier(qy))) => {
+            match (qualifier_rank(x), qualifier_rank(y)) {
+                (Some(rx), Some(ry)) => rx.cmp(&ry),
+                // Unknown qualifiers sort after all known ones, alphabetically
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => qx.cmp(qy),
+            }
+        }
+    }
+}
+
+impl Ord for ComparableVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.items.len().max(other.items.len());
+        for i in 0..len {
+            let ord = compare_items(self.items.get(i), other.items.get(i));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for ComparableVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ComparableVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ComparableVersion {}
+
+/// The part of a version that must match for binary compatibility under the
+/// early-semver scheme most Scala libraries follow: the major version, or
+/// major and minor while the major version is 0.
+pub fn binary_epoch(version: &str) -> (u64, Option<u64>) {
+    let mut numbers = version
+        .split(['.', '-'])
+        .map(|part| part.parse::<u64>().ok());
+    let major = numbers.next().flatten().unwrap_or(0);
+    if major == 0 {
+        (0, numbers.next().flatten())
+    } else {
+        (major, None)
+    }
+}
+
+/// Whether `version` uses Maven's range syntax, e.g. "[1.0,2.0)".
+pub fn is_range(version: &str) -> bool {
+    version.starts_with('[') || version.starts_with('(')
+}
+
+/// A Maven version range: one or more comma-separated intervals such as
+/// `[1.0,2.0)`, `[1.5,)`, `(,1.0]` or `[1.2]`, matching a version in any of them.
+#[derive(Debug, Clone)]
+pub struct VersionRange {
+    intervals: Vec<Interval>,
+}
+
+#[derive(Debug, Clone)]
+struct Interval {
+    /// Bound and whether it is inclusive; `None` when unbounded
+    lower: Option<(ComparableVersion, bool)>,
+    upper: Option<(ComparableVersion, bool)>,
+}
+
+impl VersionRange {
+    pub fn parse(range: &str) -> Result<Self> {
+        let mut intervals = Vec::new();
+        let mut rest = range.trim();
+        while !rest.is_empty() {
+            let lower_inclusive = match rest.chars().next() {
+                Some('[') => true,
+                Some('(') => false,
+                _ => bail!("invalid version range '{range}'"),
+            };
+            let Some(end) = rest.find([']', ')']) else {
+                bail!("invalid version range '{range}': missing closing bracket");
+            };
+            let upper_inclusive = rest[end..].starts_with(']');
+            let inner = &rest[1..end];
+
+            let bound = |s: &str, inclusive: bool| {
+                let s = s.trim();
+                (!s.is_empty()).then(|| (ComparableVersion::parse(s), inclusive))
+            };
+            let interval = match inner.split_once(',') {
+                Some((lower, upper)) => Interval {
+                    lower: bound(lower, lower_inclusive),
+                    upper: bound(upper, upper_inclusive),
+                },
+                // "[1.0]" is exactly 1.0
+                None if lower_inclusive && upper_inclusive && !inner.trim().is_empty() => Interval {
+                    lower: bound(inner, true),
+                    upper: bound(inner, true),
+                },
+                None => bail!("invalid version range '{range}'"),
+            };
+            intervals.push(interval);
+
+            rest = rest[end + 1..].trim_start();
+            if let Some(next) = rest.strip_prefix(',') {
+                rest = next.trim_start();
+            } else if !rest.is_empty() {
+                bail!("invalid version range '{range}'");
+            }
+        }
+        if intervals.is_empty() {
+            bail!("invalid version range '{range}'");
+        }
+        Ok(Self { intervals })
+    }
+
+    pub fn contains(&self, version: &ComparableVersion) -> bool {
+        self.intervals.iter().any(|i| {
+            let above = match &i.lower {
+                Some((v, true)) => version >= v,
+                Some((v, false)) => version > v,
+                None => true,
+            };
+            let below = match &i.upper {
+                Some((v, true)) => version <= v,
+                Some((v, false)) => version < v,
+                None => true,
+            };
+            above && below
+        })
+    }
+
+    /// The highest release (non-snapshot) version in `versions` within the range.
+    pub fn highest_release<'a>(&self, versions: &'a [String]) -> Option<&'a String> {
+        versions
+            .iter()
+            .filter(|v| !v.to_ascii_uppercase().ends_with("SNAPSHOT"))
+            .map(|v| (ComparableVersion::parse(v), v))
+            .filter(|(parsed, _)| self.contains(parsed))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v)
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::asm;
+use crate::compile;
+use crate::config::SbConfig;
+use crate::run;
+
+/// `sb native`: compile the project to a native executable at
+/// `.sb/native/<name>` with GraalVM's native-image. The input is the
+/// runtime classpath, or the `sb asm` JAR with `from_assembly`.
+pub fn native(config: &SbConfig, project_root: &Path, main: Option<&str>, from_assembly: bool) -> Result<PathBuf> {
+    let native_image = native_image(config)?;
+    let out_dir = project_root.join(".sb").join("native");
+    std::fs::create_dir_all(&out_dir)?;
+    let output = out_dir.join(&config.project.name);
+
+    let mut cmd = Command::new(&native_image);
+    if from_assembly {
+        let jar = asm::assemble(config, project_root, main, false)?;
+        cmd.arg("-jar").arg(jar);
+    } else {
+        let result = compile::compile(config, project_root)?;
+        let main_class = run::main_class(config, project_root, &result.resolved, main)?;
+        // Includes the resources, where `sb native agent` writes its configuration
+        let classpath = run::runtime_classpath(config, project_root, &result.resolved);
+        cmd.arg("-cp").arg(classpath).arg(main_class);
+    }
+    // Without a JVM to fall back on, a build that cannot be fully native fails
+    // instead of producing an image that still needs java
+    cmd.arg("--no-fallback").arg("-o").arg(&output).args(&config.native.args);
+
+    eprintln!("Building native image...");
+    let status = cmd
+        .status()
+        .with_context(|| format!("failed to run {}", native_image.display()))?;
+    if !status.success() {
+        bail!("native-image failed");
+    }
+    let mut executable = output.into_os_string();
+    executable.push(std::env::consts::EXE_SUFFIX);
+    Ok(executable.into())
+}
+
+/// `sb native agent`: run the main class under GraalVM's tracing agent, which
+/// records the reflection, resource and proxy use native-image cannot see
+/// into `src/main/resources/META-INF/native-image`, merging with what is
+/// already there.
+pub fn agent(config: &SbConfig, project_root: &Path, main: Option<&str>, args: &[String]) -> Result<()> {
+    let native_image = native_image(config)?;
+    // The agent ships with GraalVM's own java
+    let java = native_image.with_file_name(format!("java{}", std::env::consts::EXE_SUFFIX));
+    if !java.is_file() {
+        bail!("no java next to {}; the tracing agent needs GraalVM's java", native_image.display());
+    }
+
+    let result = compile::compile(config, project_root)?;
+    let main_class = run::main_class(config, project_root, &result.resolved, main)?;
+    let config_dir = SbConfig::resource_dir(project_root).join("META-INF").join("native-image");
+    std::fs::create_dir_all(&config_dir)?;
+
+    let mut cmd = Command::new(&java);
+    cmd.arg(format!("-agentlib:native-image-agent=config-merge-dir={}", config_dir.display()));
+    if accepts_option(&java, UNSAFE_MEMORY_ACCESS) {
+        cmd.arg(UNSAFE_MEMORY_ACCESS);
+    }
+    let status = cmd
+        .arg("-cp")
+        .arg(run::runtime_classpath(config, project_root, &result.resolved))
+        .arg(&main_class)
+        .args(args)
+        .status()
+        .with_context(|| format!("failed to run {}", java.display()))?;
+    if !status.success() {
+        bail!("{main_class} exited with {status}");
+    }
+    eprintln!("Native image configuration: {}", config_dir.display());
+    Ok(())
+}
+
+const UNSAFE_MEMORY_ACCESS: &str = "--sun-misc-unsafe-memory-access=allow";
+
+/// Whether `java` starts with `option`. GraalVM releases track their JDK, and
+/// those for JDKs before 23 refuse `--sun-misc-unsafe-memory-access`.
+fn accepts_option(java: &Path, option: &str) -> bool {
+    Command::new(java)
+        .args([option, "-version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The native-image executable: `[native] native-image`, or the one in
+/// `GRAALVM_HOME`, `JAVA_HOME` or `PATH`.
+fn native_image(config: &SbConfig) -> Result<PathBuf> {
+    if let Some(path) = &config.native.native_image {
+        if !path.is_file() {
+            bail!("native-image not found at {}", path.display());
+        }
+        return Ok(path.clone());
+    }
+    let name = format!("native-image{}", std::env::consts::EXE_SUFFIX);
+    let homes = ["GRAALVM_HOME", "JAVA_HOME"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .map(|home| PathBuf::from(home).join("bin"));
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    homes
+        .chain(std::env::split_paths(&path))
+        .map(|dir| dir.join(&name))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "native-image not found; install GraalVM and set GRAALVM_HOME, or set native-image in [native]"
+            )
+        })
+}
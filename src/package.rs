@@ -0,0 +1,203 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+use zip::write::FileOptions;
+use zip::{DateTime, ZipWriter};
+
+use crate::compile;
+use crate::config::SbConfig;
+use crate::resolve;
+
+/// The JARs written by `sb package`.
+pub struct Packaged {
+    pub jar: PathBuf,
+    pub sources: PathBuf,
+    pub javadoc: PathBuf,
+}
+
+/// `sb package`: write a thin JAR of the compiled classes with its sources
+/// and javadoc JARs to `.sb/`. With `class_path`, the dependency JARs are
+/// copied to `.sb/lib/` and listed in the manifest, so the JAR runs with
+/// `java -jar`.
+pub fn package(config: &SbConfig, project_root: &Path, class_path: bool) -> Result<Packaged> {
+    let project = &config.project;
+    let result = compile::compile(config, project_root)?;
+    let classes_dir = SbConfig::classes_dir(project_root);
+    let out_dir = project_root.join(".sb");
+    let base = format!("{}-{}", project.name, project.version);
+
+    let mut manifest = vec![
+        ("Manifest-Version", "1.0".to_string()),
+        ("Implementation-Title", project.name.clone()),
+        ("Implementation-Version", project.version.clone()),
+    ];
+    if let Some(main_class) = &project.main_class {
+        manifest.push(("Main-Class", main_class.clone()));
+    }
+    if class_path {
+        let jars = copy_libs(&result.resolved.run_classpath(), &out_dir.join("lib"))?;
+        if !jars.is_empty() {
+            manifest.push(("Class-Path", jars.join(" ")));
+        }
+    }
+
+    let jar = out_dir.join(format!("{base}.jar"));
+    write_jar(&jar, &manifest, &[&classes_dir, &SbConfig::resource_output_dir(project_root)])?;
+
+    let sources = out_dir.join(format!("{base}-sources.jar"));
+    let source_dirs = [SbConfig::source_dir(project_root), SbConfig::resource_dir(project_root)];
+    write_jar(&sources, &basic_manifest(config), &[&source_dirs[0], &source_dirs[1]])?;
+
+    let doc_dir = out_dir.join("doc");
+    scaladoc(config, &result.resolved.compile_classpath(), &classes_dir, &doc_dir)?;
+    let javadoc = out_dir.join(format!("{base}-javadoc.jar"));
+    write_jar(&javadoc, &basic_manifest(config), &[&doc_dir])?;
+
+    Ok(Packaged { jar, sources, javadoc })
+}
+
+fn basic_manifest(config: &SbConfig) -> Vec<(&'static str, String)> {
+    vec![
+        ("Manifest-Version", "1.0".to_string()),
+        ("Implementation-Title", config.project.name.clone()),
+        ("Implementation-Version", config.project.version.clone()),
+    ]
+}
+
+/// Copy the JARs of `classpath` into `lib_dir`, returning their paths relative
+/// to the directory above it, as the manifest's Class-Path expects.
+fn copy_libs(classpath: &str, lib_dir: &Path) -> Result<Vec<String>> {
+    if lib_dir.exists() {
+        std::fs::remove_dir_all(lib_dir)?;
+    }
+    std::fs::create_dir_all(lib_dir)?;
+    let mut names = HashSet::new();
+    let mut entries = Vec::new();
+    for jar in classpath.split(':').filter(|p| p.ends_with(".jar")) {
+        let path = Path::new(jar);
+        let mut name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        // Two artifacts with the same name from different groups
+        while !names.insert(name.clone()) {
+            name = format!("_{name}");
+        }
+        std::fs::copy(path, lib_dir.join(&name))?;
+        entries.push(format!("lib/{name}"));
+    }
+    Ok(entries)
+}
+
+/// Generate the API documentation from the TASTy files in `classes_dir`.
+fn scaladoc(config: &SbConfig, classpath: &str, classes_dir: &Path, out_dir: &Path) -> Result<()> {
+    let sv = &config.project.scala_version;
+    let scaladoc_cp = resolve::tool_classpath(config, &format!("org.scala-lang:scala3-scaladoc_3:{sv}"))?;
+
+    let mut tasty_files: Vec<PathBuf> = WalkDir::new(classes_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "tasty"))
+        .collect();
+    tasty_files.sort();
+
+    if out_dir.exists() {
+        std::fs::remove_dir_all(out_dir)?;
+    }
+    std::fs::create_dir_all(out_dir)?;
+    if tasty_files.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("Generating API documentation...");
+    let output = Command::new("java")
+        .arg("--sun-misc-unsafe-memory-access=allow")
+        .arg("-cp")
+        .arg(&scaladoc_cp)
+        .arg("dotty.tools.scaladoc.Main")
+        .arg("-d")
+        .arg(out_dir)
+        .arg("-project")
+        .arg(&config.project.name)
+        .arg("-project-version")
+        .arg(&config.project.version)
+        .arg("-classpath")
+        .arg(classpath)
+        .args(&tasty_files)
+        .output()?;
+    if !output.status.success() {
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        bail!("scaladoc failed:\n{text}");
+    }
+    Ok(())
+}
+
+/// Write a JAR of the manifest and every file under `dirs`; where two have
+/// the same path, the first wins. Entries are sorted and carry fixed
+/// timestamps and permissions, so the same inputs always give the same bytes.
+pub fn write_jar(path: &Path, manifest: &[(&str, String)], dirs: &[&Path]) -> Result<()> {
+    let mut zip = ZipWriter::new(std::fs::File::create(path)?);
+    let file_options = FileOptions::<()>::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644);
+    let dir_options = file_options.unix_permissions(0o755);
+
+    zip.add_directory("META-INF/", dir_options)?;
+    zip.start_file("META-INF/MANIFEST.MF", file_options)?;
+    zip.write_all(manifest_text(manifest).as_bytes())?;
+
+    let mut entries = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        for entry in WalkDir::new(dir).min_depth(1) {
+            let entry = entry?;
+            let rel = entry.path().strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+            entries.push((rel, entry.file_type().is_dir(), entry.into_path()));
+        }
+    }
+    // Sorting is stable, so the first directory's copy comes first
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut seen = HashSet::new();
+    for (rel, is_dir, source) in entries {
+        if rel == "META-INF" || rel == "META-INF/MANIFEST.MF" || !seen.insert(rel.clone()) {
+            continue;
+        }
+        if is_dir {
+            zip.add_directory(format!("{rel}/"), dir_options)?;
+        } else {
+            zip.start_file(rel, file_options)?;
+            zip.write_all(&std::fs::read(&source)?)?;
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// MANIFEST.MF content. Lines longer than 72 bytes continue on the next line
+/// after a single space, as the JAR specification requires.
+pub fn manifest_text(attributes: &[(&str, String)]) -> String {
+    let mut text = String::new();
+    for (name, value) in attributes {
+        let line = format!("{name}: {value}");
+        let mut rest = line.as_str();
+        let mut limit = 72;
+        loop {
+            let mut end = rest.len().min(limit);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.push_str(&rest[..end]);
+            text.push_str("\r\n");
+            rest = &rest[end..];
+            if rest.is_empty() {
+                break;
+            }
+            text.push(' ');
+            limit = 71;
+        }
+    }
+    text.push_str("\r\n");
+    text
+}
@@ -0,0 +1,258 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::config::SbConfig;
+use crate::maven::checksum;
+use crate::maven::coord::MavenCoord;
+use crate::maven::fetch;
+use crate::maven::metadata;
+use crate::maven::repository::{self, Repository};
+use crate::maven::resolve::Root;
+use crate::package;
+use crate::resolve;
+
+/// Where `sb publish-local` installs the library.
+#[derive(Debug, Clone, Copy)]
+pub enum LocalRepository {
+    /// sb's download cache, so other sb projects resolve it, even offline
+    Cache,
+    /// `~/.m2/repository`, for Maven, Gradle and sbt
+    M2,
+}
+
+/// The files of one published version, by file name: the JAR, the sources
+/// and javadoc JARs, and the POM.
+struct Publication {
+    coord: MavenCoord,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// `sb publish-local`: write the library into a local Maven repository.
+pub fn publish_local(config: &SbConfig, project_root: &Path, target: LocalRepository) -> Result<()> {
+    let publication = prepare(config, project_root)?;
+    let base = match target {
+        LocalRepository::Cache => fetch::cache_root()?,
+        LocalRepository::M2 => dirs::home_dir()
+            .context("could not determine home directory")?
+            .join(".m2")
+            .join("repository"),
+    };
+    let dir = base.join(publication.coord.repo_path());
+    for (name, bytes) in &publication.files {
+        fetch::write_atomic(&dir.join(name), bytes)?;
+    }
+    if let LocalRepository::Cache = target {
+        // Recorded as the repository of the JAR in the sb.lock of projects using it
+        let jar = publication.coord.artifact_filename();
+        fetch::write_atomic(&dir.join(format!("{jar}.origin")), b"local")?;
+    }
+    eprintln!("Published {} to {}", publication.coord, dir.display());
+    Ok(())
+}
+
+/// `sb publish --repo <name>`: upload the library, its checksums and an
+/// updated `maven-metadata.xml` to a configured repository.
+pub fn publish(config: &SbConfig, project_root: &Path, repo_name: &str) -> Result<()> {
+    if resolve::offline(config) {
+        bail!("cannot publish to '{repo_name}' in offline mode");
+    }
+    let repo = repository::named(&config.repositories, repo_name)?;
+    let publication = prepare(config, project_root)?;
+    let coord = &publication.coord;
+    let agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .new_agent();
+
+    let dir = format!("{}/{}", repo.url, coord.repo_path());
+    for (name, bytes) in &publication.files {
+        upload(&agent, &repo, &format!("{dir}/{name}"), bytes)?;
+        eprintln!("Uploaded {name}");
+    }
+
+    // Version ranges are resolved from the metadata, so list the new version there
+    let url = format!(
+        "{}/{}/{}/maven-metadata.xml",
+        repo.url,
+        coord.group_id.replace('.', "/"),
+        coord.artifact_id
+    );
+    let mut versions = match get_optional(&agent, &repo, &url)? {
+        Some(xml) => metadata::parse_versions(&xml)?,
+        None => Vec::new(),
+    };
+    if !versions.contains(&coord.version) {
+        versions.push(coord.version.clone());
+    }
+    let xml = metadata::render(&coord.group_id, &coord.artifact_id, &versions);
+    upload(&agent, &repo, &url, xml.as_bytes())?;
+
+    eprintln!("Published {coord} to {}", repo.name);
+    Ok(())
+}
+
+/// Package the project and read the files to publish.
+fn prepare(config: &SbConfig, project_root: &Path) -> Result<Publication> {
+    let group_id = config
+        .project
+        .group_id
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("group-id is required in sb.toml to publish"))?;
+    let coord = MavenCoord::new(group_id, format!("{}_3", config.project.name), &config.project.version);
+
+    let packaged = package::package(config, project_root, false)?;
+    let pom = pom_xml(config, project_root, &coord)?;
+
+    Ok(Publication {
+        files: vec![
+            (coord.artifact_filename(), std::fs::read(&packaged.jar)?),
+            (coord.with_variant(Some("sources"), "jar").artifact_filename(), std::fs::read(&packaged.sources)?),
+            (coord.with_variant(Some("javadoc"), "jar").artifact_filename(), std::fs::read(&packaged.javadoc)?),
+            (coord.with_variant(None, "pom").artifact_filename(), pom.into_bytes()),
+        ],
+        coord,
+    })
+}
+
+/// The POM: coordinates, license and SCM from `[project]`, and the
+/// dependencies of sb.toml with their Scala suffixes applied.
+fn pom_xml(config: &SbConfig, project_root: &Path, coord: &MavenCoord) -> Result<String> {
+    let project = &config.project;
+    let sv = &project.scala_version;
+
+    let mut deps = vec![Root::new(MavenCoord::new("org.scala-lang", "scala3-library_3", sv.as_str()))];
+    // In a workspace, `config` also holds the dependencies inherited from
+    // upstream members; those come in through the members' own POMs instead
+    let own = if config.upstream.is_empty() {
+        None
+    } else {
+        Some(SbConfig::load(project_root)?)
+    };
+    let own_project = own.as_ref().map_or(project, |c| &c.project);
+    for dep in &own_project.dependencies {
+        deps.push(resolve::root(dep, sv)?);
+    }
+    for up in &config.upstream {
+        let up = SbConfig::load(up)?;
+        let Some(group_id) = &up.project.group_id else {
+            bail!(
+                "workspace member '{}' needs a group-id so that {} can depend on it once published",
+                up.project.name,
+                project.name
+            );
+        };
+        let up_coord = MavenCoord::new(group_id, format!("{}_3", up.project.name), up.project.version);
+        deps.push(Root::new(up_coord));
+    }
+    for dep in &own_project.test_dependencies {
+        let mut root = resolve::root(dep, sv)?;
+        root.scope = "test".to_string();
+        deps.push(root);
+    }
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <project xmlns=\"http://maven.apache.org/POM/4.0.0\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"http://maven.apache.org/POM/4.0.0 https://maven.apache.org/xsd/maven-4.0.0.xsd\">\n\
+         \x20 <modelVersion>4.0.0</modelVersion>\n",
+    );
+    xml.push_str(&format!("  <groupId>{}</groupId>\n", escape(&coord.group_id)));
+    xml.push_str(&format!("  <artifactId>{}</artifactId>\n", escape(&coord.artifact_id)));
+    xml.push_str(&format!("  <version>{}</version>\n", escape(&coord.version)));
+    xml.push_str("  <packaging>jar</packaging>\n");
+    xml.push_str(&format!("  <name>{}</name>\n", escape(&project.name)));
+    if let Some(license) = &project.license {
+        xml.push_str(&format!(
+            "  <licenses>\n    <license>\n      <name>{}</name>\n    </license>\n  </licenses>\n",
+            escape(license)
+        ));
+    }
+    if let Some(scm) = &project.scm {
+        xml.push_str(&format!("  <scm>\n    <url>{}</url>\n  </scm>\n", escape(scm)));
+    }
+    xml.push_str("  <dependencies>\n");
+    for root in &deps {
+        xml.push_str(&dependency_xml(root));
+    }
+    xml.push_str("  </dependencies>\n</project>\n");
+    Ok(xml)
+}
+
+fn dependency_xml(root: &Root) -> String {
+    let c = &root.coord;
+    let mut xml = String::from("    <dependency>\n");
+    xml.push_str(&format!("      <groupId>{}</groupId>\n", escape(&c.group_id)));
+    xml.push_str(&format!("      <artifactId>{}</artifactId>\n", escape(&c.artifact_id)));
+    xml.push_str(&format!("      <version>{}</version>\n", escape(&c.version)));
+    if let Some(classifier) = &c.classifier {
+        xml.push_str(&format!("      <classifier>{}</classifier>\n", escape(classifier)));
+    }
+    if c.packaging != "jar" {
+        xml.push_str(&format!("      <type>{}</type>\n", escape(&c.packaging)));
+    }
+    if root.scope != "compile" {
+        xml.push_str(&format!("      <scope>{}</scope>\n", root.scope));
+    }
+    let mut exclusions = root.exclusions.clone();
+    if !root.transitive {
+        exclusions = vec![("*".to_string(), "*".to_string())];
+    }
+    if !exclusions.is_empty() {
+        xml.push_str("      <exclusions>\n");
+        for (group, artifact) in &exclusions {
+            xml.push_str(&format!(
+                "        <exclusion>\n          <groupId>{}</groupId>\n          <artifactId>{}</artifactId>\n        </exclusion>\n",
+                escape(group),
+                escape(artifact)
+            ));
+        }
+        xml.push_str("      </exclusions>\n");
+    }
+    xml.push_str("    </dependency>\n");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// PUT `bytes` to `url`, followed by its checksum files.
+fn upload(agent: &ureq::Agent, repo: &Repository, url: &str, bytes: &[u8]) -> Result<()> {
+    put(agent, repo, url, bytes)?;
+    for algorithm in checksum::ALGORITHMS {
+        let digest = algorithm.digest(bytes);
+        put(agent, repo, &format!("{url}.{}", algorithm.extension()), digest.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn put(agent: &ureq::Agent, repo: &Repository, url: &str, bytes: &[u8]) -> Result<()> {
+    let mut request = agent.put(url);
+    if let Some(auth) = repo.authorization()? {
+        request = request.header("Authorization", auth);
+    }
+    let response = request
+        .send(bytes)
+        .map_err(|e| anyhow::anyhow!("HTTP PUT {url} failed: {e}"))?;
+    match response.status().as_u16() {
+        200 | 201 | 204 => Ok(()),
+        status => bail!("HTTP {status} for PUT {url}"),
+    }
+}
+
+/// GET a small text file, or `None` if the repository does not have it.
+fn get_optional(agent: &ureq::Agent, repo: &Repository, url: &str) -> Result<Option<String>> {
+    let mut request = agent.get(url);
+    if let Some(auth) = repo.authorization()? {
+        request = request.header("Authorization", auth);
+    }
+    let mut response = request
+        .call()
+        .map_err(|e| anyhow::anyhow!("HTTP GET {url} failed: {e}"))?;
+    match response.status().as_u16() {
+        200 => Ok(Some(response.body_mut().read_to_string()?)),
+        404 => Ok(None),
+        status => bail!("HTTP {status} for {url}"),
+    }
+}
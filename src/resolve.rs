@@ -1,46 +1,102 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use indicatif::MultiProgress;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::Path;
 
-use crate::maven::fetch::ProgressTracker;
+use crate::maven::coord::MavenCoord;
+use crate::maven::fetch::{MissingArtifacts, ProgressTracker};
+use crate::maven::resolve::{Eviction, Resolution, Root, ROOT_REQUESTER};
+use crate::maven::version;
+use crate::maven::repository;
+use crate::maven::{Artifact, Pins};
 
 use crate::cache;
-use crate::config::SbConfig;
+use crate::config::{ConflictResolution, Dependency, SbConfig};
+use crate::lock::{Lockfile, Scope, LOCK_FILE};
 use crate::maven;
 
 #[derive(Debug, Clone)]
 pub struct ResolvedClasspath {
     pub compiler_cp: String,
     pub user_cp: String,
+    /// Test-only dependencies (plus sbt test-interface); empty when none are declared.
+    pub test_cp: String,
+    /// Dependencies only needed to compile (`scope = "provided"`).
+    pub provided_cp: String,
+    /// Dependencies only needed to run (`scope = "runtime"`).
+    pub runtime_cp: String,
 }
 
 impl ResolvedClasspath {
     fn serialize(&self) -> String {
-        format!("{}\n{}", self.compiler_cp, self.user_cp)
+        format!(
+            "{}\n{}\n{}\n{}\n{}",
+            self.compiler_cp, self.user_cp, self.test_cp, self.provided_cp, self.runtime_cp
+        )
     }
 
     fn deserialize(s: &str) -> Option<Self> {
         let mut lines = s.lines();
         let compiler_cp = lines.next()?.to_string();
         let user_cp = lines.next()?.to_string();
-        Some(Self { compiler_cp, user_cp })
+        let test_cp = lines.next().unwrap_or_default().to_string();
+        let provided_cp = lines.next().unwrap_or_default().to_string();
+        let runtime_cp = lines.next().unwrap_or_default().to_string();
+        Some(Self { compiler_cp, user_cp, test_cp, provided_cp, runtime_cp })
+    }
+
+    /// What `src/main/scala` is compiled against.
+    pub fn compile_classpath(&self) -> String {
+        join_classpath(&[&self.user_cp, &self.provided_cp])
+    }
+
+    /// What the program runs with and `sb asm` packages.
+    pub fn run_classpath(&self) -> String {
+        join_classpath(&[&self.user_cp, &self.runtime_cp])
+    }
+
+    /// Every main dependency, whatever its scope, for compiling and running tests.
+    pub fn main_classpath(&self) -> String {
+        join_classpath(&[&self.user_cp, &self.provided_cp, &self.runtime_cp])
     }
 }
 
+/// Coordinate of the sbt test-interface, which the `sb test` runner is compiled against.
+pub const TEST_INTERFACE: &str = "org.scala-sbt:test-interface:1.0";
+
 pub fn dep_hash(config: &SbConfig) -> u64 {
-    let mut items: Vec<String> = config.project.dependencies.clone();
+    let mut items: Vec<String> = config.project.dependencies.iter().map(Dependency::hash_key).collect();
     items.sort();
     items.insert(0, config.project.scala_version.clone());
+    let mut test_items: Vec<String> = config
+        .project
+        .test_dependencies
+        .iter()
+        .map(|d| format!("test:{}", d.hash_key()))
+        .collect();
+    test_items.sort();
+    items.extend(test_items);
+    // Only the non-default strategy is hashed, so existing locks stay fresh
+    if config.project.conflict_resolution == ConflictResolution::Highest {
+        items.push("conflict:highest".to_string());
+    }
     cache::hash_strings(&items)
 }
 
 pub fn resolve_classpath(config: &SbConfig, project_root: &Path) -> Result<ResolvedClasspath> {
-    let hash = dep_hash(config);
-    let hash_str = hash.to_string();
+    let lock = Lockfile::load(project_root)?;
+    let fresh = lock.as_ref().is_some_and(|(l, _)| l.is_fresh(config));
+    if config.flags.locked && !fresh {
+        match lock {
+            Some(_) => bail!("{LOCK_FILE} is out of date with sb.toml; run `sb update` to refresh it"),
+            None => bail!("{LOCK_FILE} is missing; run `sb update` to create it"),
+        }
+    }
 
     // Check cache
-    if let Some(cached_hash) = cache::read_cache(project_root, "dep-hash") {
-        if cached_hash == hash_str {
+    if let Some((_, content)) = lock.as_ref().filter(|_| fresh) {
+        let key = classpath_key(config, content);
+        if cache::read_cache(project_root, "classpath-key").as_deref() == Some(key.as_str()) {
             if let Some(cached_cp) = cache::read_cache(project_root, "classpath") {
                 if let Some(resolved) = ResolvedClasspath::deserialize(&cached_cp) {
                     return Ok(resolved);
@@ -49,45 +105,458 @@ pub fn resolve_classpath(config: &SbConfig, project_root: &Path) -> Result<Resol
         }
     }
 
+    let (resolved, content) = match lock {
+        Some((lock, content)) if fresh => (from_lock(config, &lock)?, content),
+        Some((lock, _)) => resolve_and_lock(config, project_root, Some(&lock), None)?,
+        None => resolve_and_lock(config, project_root, None, None)?,
+    };
+
+    // Write cache
+    cache::write_cache(project_root, "classpath-key", &classpath_key(config, &content))?;
+    cache::write_cache(project_root, "classpath", &resolved.serialize())?;
+
+    Ok(resolved)
+}
+
+/// Re-resolve dependencies and rewrite sb.lock. With `dep`, only artifacts
+/// matching it may change; everything else keeps its locked version.
+pub fn update(config: &SbConfig, project_root: &Path, dep: Option<&str>) -> Result<()> {
+    let old = Lockfile::load(project_root)?.map(|(lock, _)| lock);
+    if let Some(dep) = dep {
+        match &old {
+            Some(lock) if lock.contains(dep) => {}
+            Some(_) => bail!("no dependency matching '{dep}' in {LOCK_FILE}"),
+            None => bail!("{LOCK_FILE} is missing; run `sb update` without arguments to create it"),
+        }
+    }
+    let (resolved, content) = resolve_and_lock(config, project_root, old.as_ref(), dep)?;
+    cache::write_cache(project_root, "classpath-key", &classpath_key(config, &content))?;
+    cache::write_cache(project_root, "classpath", &resolved.serialize())?;
+    Ok(())
+}
+
+fn classpath_key(config: &SbConfig, lock_content: &str) -> String {
+    cache::hash_strings(&[dep_hash(config).to_string(), lock_content.to_string()]).to_string()
+}
+
+/// Download exactly the artifacts recorded in the lock and check their checksums.
+fn from_lock(config: &SbConfig, lock: &Lockfile) -> Result<ResolvedClasspath> {
+    let repositories = repository::effective(&config.repositories);
+    let offline = offline(config);
+    let coords = |scope| -> Result<Vec<MavenCoord>> {
+        lock.scope(scope).map(|a| MavenCoord::parse(&a.coord)).collect()
+    };
+    let compiler = coords(Scope::Compiler)?;
+    let mut user = coords(Scope::Compile)?;
+    user.extend(coords(Scope::Provided)?);
+    user.extend(coords(Scope::Runtime)?);
+    let test = coords(Scope::Test)?;
+
+    let mp = MultiProgress::new();
+    let tracker = ProgressTracker::new(mp);
+    let (compiler_result, user_result, test_result) = std::thread::scope(|s| {
+        let h1 = s.spawn(|| maven::fetch_artifacts(&compiler, &repositories, offline, &tracker));
+        let h2 = s.spawn(|| maven::fetch_artifacts(&user, &repositories, offline, &tracker));
+        let h3 = s.spawn(|| maven::fetch_artifacts(&test, &repositories, offline, &tracker));
+        (
+            h1.join().expect("compiler fetch panicked"),
+            h2.join().expect("user fetch panicked"),
+            h3.join().expect("test fetch panicked"),
+        )
+    });
+    let (compiler, user, test) = join_scopes(compiler_result, user_result, test_result)?;
+    let locked_in = |scope, artifact: &Artifact| {
+        let coord = artifact.coord.to_string();
+        lock.scope(scope).any(|a| a.coord == coord)
+    };
+    let (provided, user): (Vec<Artifact>, Vec<Artifact>) =
+        user.into_iter().partition(|a| locked_in(Scope::Provided, a));
+    let (runtime, user): (Vec<Artifact>, Vec<Artifact>) =
+        user.into_iter().partition(|a| locked_in(Scope::Runtime, a));
+
+    lock.verify(Scope::Compiler, &compiler)?;
+    lock.verify(Scope::Compile, &user)?;
+    lock.verify(Scope::Provided, &provided)?;
+    lock.verify(Scope::Runtime, &runtime)?;
+    lock.verify(Scope::Test, &test)?;
+
+    Ok(ResolvedClasspath {
+        compiler_cp: artifact_classpath(&compiler),
+        user_cp: artifact_classpath(&user),
+        test_cp: artifact_classpath(&test),
+        provided_cp: artifact_classpath(&provided),
+        runtime_cp: artifact_classpath(&runtime),
+    })
+}
+
+/// The dependencies each resolution starts from: the compiler, the Scala
+/// library plus `dependencies`, and `test-dependencies`.
+fn root_deps(config: &SbConfig) -> Result<(Vec<Root>, Vec<Root>, Vec<Root>)> {
     let sv = &config.project.scala_version;
+    let plain = |coord: &str| MavenCoord::parse(coord).map(Root::new);
 
-    let compiler_deps = vec![format!("org.scala-lang:scala3-compiler_3:{sv}")];
-    let mut user_deps = vec![format!("org.scala-lang:scala3-library_3:{sv}")];
+    let compiler_deps = vec![plain(&format!("org.scala-lang:scala3-compiler_3:{sv}"))?];
+    let mut user_deps = vec![plain(&format!("org.scala-lang:scala3-library_3:{sv}"))?];
     for dep in &config.project.dependencies {
-        user_deps.push(resolve_dep_coord(dep, sv));
+        user_deps.push(root(dep, sv)?);
     }
+    let mut test_deps = Vec::new();
+    if !config.project.test_dependencies.is_empty() {
+        test_deps.push(plain(TEST_INTERFACE)?);
+        for dep in &config.project.test_dependencies {
+            test_deps.push(root(dep, sv)?);
+        }
+    }
+    Ok((compiler_deps, user_deps, test_deps))
+}
 
+pub fn root(dep: &Dependency, scala_version: &str) -> Result<Root> {
+    let mut coord = MavenCoord::parse(&resolve_dep_coord(&dep.coord, dep.cross.as_deref(), scala_version)?)?;
+    if dep.classifier.is_some() {
+        coord.classifier = dep.classifier.clone();
+    }
+    let exclusions = dep
+        .exclude
+        .iter()
+        .flat_map(|e| match e.split_once("::") {
+            // Either Scala binary version may come in transitively
+            Some((group, name)) => vec![
+                (group.to_string(), format!("{name}_3")),
+                (group.to_string(), format!("{name}_2.13")),
+            ],
+            None => e
+                .split_once(':')
+                .map(|(group, artifact)| (group.to_string(), artifact.to_string()))
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+    Ok(Root {
+        coord,
+        exclusions,
+        transitive: dep.transitive,
+        scope: dep.scope.as_str().to_string(),
+    })
+}
+
+/// Split the main resolution by the scope of the sb.toml entries that pull
+/// each artifact in, returning (compile, provided, runtime). Anything a
+/// compile-scope entry needs, or that is needed both to compile and at
+/// runtime, goes on every classpath.
+fn split_scopes(
+    artifacts: Vec<Artifact>,
+    resolution: &Resolution,
+) -> (Vec<Artifact>, Vec<Artifact>, Vec<Artifact>) {
+    let compile = reachable(resolution, "compile");
+    let provided = reachable(resolution, "provided");
+    let runtime = reachable(resolution, "runtime");
+
+    let (mut c, mut p, mut r) = (Vec::new(), Vec::new(), Vec::new());
+    for artifact in artifacts {
+        let module = format!("{}:{}", artifact.coord.group_id, artifact.coord.artifact_id);
+        let in_provided = provided.contains(&module);
+        let in_runtime = runtime.contains(&module);
+        if compile.contains(&module) || in_provided == in_runtime {
+            c.push(artifact);
+        } else if in_provided {
+            p.push(artifact);
+        } else {
+            r.push(artifact);
+        }
+    }
+    (c, p, r)
+}
+
+/// The dependency graphs of the compile and test scopes, read from the POMs.
+/// When sb.lock is up to date its versions are pinned, so the graphs match it.
+pub fn graph(config: &SbConfig, project_root: &Path) -> Result<Vec<(Scope, Resolution)>> {
+    let lock = Lockfile::load(project_root)?.map(|(lock, _)| lock).filter(|l| l.is_fresh(config));
+    let (_, user_deps, test_deps) = root_deps(config)?;
+    let repositories = repository::effective(&config.repositories);
+    let tracker = ProgressTracker::new(MultiProgress::new());
+
+    let mut graphs = Vec::new();
+    for (scope, deps) in [(Scope::Compile, user_deps), (Scope::Test, test_deps)] {
+        if deps.is_empty() {
+            continue;
+        }
+        let pins = match &lock {
+            Some(lock) => scope_pins(lock, scope, None)?,
+            None => Pins::new(),
+        };
+        let resolution = maven::resolve_graph(
+            &deps,
+            &pins,
+            config.project.conflict_resolution,
+            &repositories,
+            offline(config),
+            &tracker,
+        )?;
+        graphs.push((scope, resolution));
+    }
+    Ok(graphs)
+}
+
+/// Resolve from the POMs and write a new sb.lock. Returns the classpath and
+/// the lock's content. With `update`, the other artifacts of `old` are pinned.
+fn resolve_and_lock(
+    config: &SbConfig,
+    project_root: &Path,
+    old: Option<&Lockfile>,
+    update: Option<&str>,
+) -> Result<(ResolvedClasspath, String)> {
+    let (compiler_deps, user_deps, test_deps) = root_deps(config)?;
+
+    let pins = |scope| -> Result<Pins> {
+        match (old, update) {
+            (Some(lock), Some(dep)) => scope_pins(lock, scope, Some(dep)),
+            _ => Ok(Pins::new()),
+        }
+    };
+    let (compiler_pins, user_pins, test_pins) = (pins(Scope::Compiler)?, pins(Scope::Compile)?, pins(Scope::Test)?);
+
+    let strategy = config.project.conflict_resolution;
+    let repositories = repository::effective(&config.repositories);
+    let offline = offline(config);
     let mp = MultiProgress::new();
     let tracker = ProgressTracker::new(mp);
 
-    // Resolve both in parallel — per-artifact progress bars appear dynamically
-    let (compiler_result, user_result) = std::thread::scope(|s| {
-        let h1 = s.spawn(|| maven::resolve_classpath(&compiler_deps, &tracker));
-        let h2 = s.spawn(|| maven::resolve_classpath(&user_deps, &tracker));
+    // Resolve all in parallel — per-artifact progress bars appear dynamically
+    let (compiler_result, user_result, test_result) = std::thread::scope(|s| {
+        let h1 = s.spawn(|| maven::resolve_artifacts(&compiler_deps, &compiler_pins, strategy, &repositories, offline, &tracker));
+        let h2 = s.spawn(|| maven::resolve_artifacts(&user_deps, &user_pins, strategy, &repositories, offline, &tracker));
+        let h3 = s.spawn(|| {
+            if test_deps.is_empty() {
+                Ok((Vec::new(), Resolution::default()))
+            } else {
+                maven::resolve_artifacts(&test_deps, &test_pins, strategy, &repositories, offline, &tracker)
+            }
+        });
         (
             h1.join().expect("compiler resolve panicked"),
             h2.join().expect("user resolve panicked"),
+            h3.join().expect("test resolve panicked"),
         )
     });
 
-    let compiler_cp = compiler_result?;
-    let user_cp = user_result?;
+    let ((compiler, _), (user, user_resolution), (test, test_resolution)) =
+        join_scopes(compiler_result, user_result, test_result)?;
+    report_evictions("compile", &user_resolution.evictions);
+    report_evictions("test", &test_resolution.evictions);
+    let (user, provided, runtime) = split_scopes(user, &user_resolution);
+    check_cross_versions(&user.iter().chain(&provided).chain(&runtime).chain(&test).collect::<Vec<_>>())?;
 
-    let resolved = ResolvedClasspath { compiler_cp, user_cp };
+    let lock = Lockfile::new(
+        config,
+        &[
+            (Scope::Compiler, &compiler),
+            (Scope::Compile, &user),
+            (Scope::Provided, &provided),
+            (Scope::Runtime, &runtime),
+            (Scope::Test, &test),
+        ],
+    )?;
+    let changes = lock.changes_from(old);
+    let content = lock.save(project_root)?;
+    if old.is_some() {
+        for change in &changes {
+            eprintln!("  {change}");
+        }
+    }
+    if old.is_none() || !changes.is_empty() {
+        eprintln!("Wrote {LOCK_FILE}");
+    }
 
-    // Write cache
-    cache::write_cache(project_root, "dep-hash", &hash_str)?;
-    cache::write_cache(project_root, "classpath", &resolved.serialize())?;
+    let resolved = ResolvedClasspath {
+        compiler_cp: artifact_classpath(&compiler),
+        user_cp: artifact_classpath(&user),
+        test_cp: artifact_classpath(&test),
+        provided_cp: artifact_classpath(&provided),
+        runtime_cp: artifact_classpath(&runtime),
+    };
+    Ok((resolved, content))
+}
 
-    Ok(resolved)
+/// Modules ("group:artifact") reachable from the sb.toml entries of `scope`.
+fn reachable(resolution: &Resolution, scope: &str) -> HashSet<String> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![ROOT_REQUESTER.to_string()];
+    while let Some(from) = stack.pop() {
+        for edge in resolution.edges.iter().filter(|e| e.from == from && !e.excluded) {
+            if from == ROOT_REQUESTER && edge.scope != scope {
+                continue;
+            }
+            if let Some(v) = &edge.selected
+                && seen.insert(edge.module.clone())
+            {
+                stack.push(format!("{}:{v}", edge.module));
+            }
+        }
+    }
+    seen
 }
 
-/// Convert a user dependency string to a full Maven coordinate.
-fn resolve_dep_coord(dep: &str, _scala_version: &str) -> String {
-    if let Some((org, rest)) = dep.split_once("::") {
-        if let Some((name, version)) = rest.split_once(':') {
-            return format!("{org}:{name}_3:{version}");
+/// Locked versions as resolver pins. The main resolution covers the compile,
+/// provided and runtime scopes together.
+fn scope_pins(lock: &Lockfile, scope: Scope, except: Option<&str>) -> Result<Pins> {
+    let mut pins = lock.pins(scope, except)?;
+    if scope == Scope::Compile {
+        pins.extend(lock.pins(Scope::Provided, except)?);
+        pins.extend(lock.pins(Scope::Runtime, except)?);
+    }
+    Ok(pins)
+}
+
+/// List the versions that lost a conflict, and warn when a Scala library was
+/// moved across a binary-incompatible version.
+fn report_evictions(scope: &str, evictions: &[Eviction]) {
+    if evictions.is_empty() {
+        return;
+    }
+    eprintln!("Evicted ({scope}):");
+    for e in evictions {
+        eprintln!(
+            "  {}:{} -> {} (requested by {})",
+            e.module,
+            e.evicted,
+            e.selected,
+            e.requested_by.join(", ")
+        );
+    }
+    for e in evictions {
+        if is_scala_library(&e.module) && version::binary_epoch(&e.evicted) != version::binary_epoch(&e.selected) {
+            eprintln!(
+                "warning: {} {} was replaced by {}, which may not be binary compatible (requested by {})",
+                e.module,
+                e.evicted,
+                e.selected,
+                e.requested_by.join(", ")
+            );
         }
     }
-    dep.to_string()
+}
+
+fn is_scala_library(module: &str) -> bool {
+    scala_suffix(module).is_some()
+}
+
+/// Scala libraries carry the Scala binary version in their artifact name,
+/// e.g. "cats-core_3" or "scalajs-dom_sjs1_2.13". Returns the name without
+/// it and the version, or `None` for Java libraries and full-version
+/// suffixes such as "_3.6.4".
+fn scala_suffix(artifact: &str) -> Option<(&str, &str)> {
+    let (name, suffix) = artifact.rsplit_once('_')?;
+    let binary = suffix == "3"
+        || suffix
+            .strip_prefix("2.")
+            .is_some_and(|minor| !minor.is_empty() && minor.chars().all(|c| c.is_ascii_digit()));
+    binary.then_some((name, suffix))
+}
+
+/// The same library built for two Scala binary versions, e.g. `cats-core_2.13`
+/// and `cats-core_3`, would put two copies of its classes on the classpath.
+fn check_cross_versions(artifacts: &[&Artifact]) -> Result<()> {
+    let mut by_library: BTreeMap<(&str, &str), BTreeSet<String>> = BTreeMap::new();
+    for artifact in artifacts {
+        let coord = &artifact.coord;
+        if let Some((name, _)) = scala_suffix(&coord.artifact_id) {
+            by_library
+                .entry((&coord.group_id, name))
+                .or_default()
+                .insert(format!("{}:{}:{}", coord.group_id, coord.artifact_id, coord.version));
+        }
+    }
+    let conflicts: Vec<String> = by_library
+        .into_iter()
+        .filter(|(_, coords)| coords.len() > 1)
+        .map(|((_, name), coords)| {
+            let coords: Vec<String> = coords.into_iter().collect();
+            format!("  {name}: {} (see `sb why {name}`)", coords.join(", "))
+        })
+        .collect();
+    if !conflicts.is_empty() {
+        bail!(
+            "conflicting Scala versions of the same library on the classpath:\n{}\n\
+             use `cross` or `exclude` in sb.toml so that only one of them remains",
+            conflicts.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Classpath of a tool run from Maven artifacts, e.g. scaladoc. Tools are not
+/// part of the project's classpath, so they are resolved without sb.lock.
+pub fn tool_classpath(config: &SbConfig, coord: &str) -> Result<String> {
+    let repositories = repository::effective(&config.repositories);
+    let tracker = ProgressTracker::new(MultiProgress::new());
+    let (artifacts, _) = maven::resolve_artifacts(
+        &[Root::new(MavenCoord::parse(coord)?)],
+        &Pins::new(),
+        ConflictResolution::Nearest,
+        &repositories,
+        offline(config),
+        &tracker,
+    )?;
+    Ok(artifact_classpath(&artifacts))
+}
+
+/// `--offline`, or `SB_OFFLINE` set to anything but "" or "0".
+pub fn offline(config: &SbConfig) -> bool {
+    config.flags.offline
+        || std::env::var("SB_OFFLINE").is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Combine the per-scope results. In offline mode several scopes can be
+/// missing files, so their lists are merged into a single error.
+fn join_scopes<T>(a: Result<T>, b: Result<T>, c: Result<T>) -> Result<(T, T, T)> {
+    let mut missing = Vec::new();
+    for e in [&a, &b, &c].into_iter().filter_map(|r| r.as_ref().err()) {
+        if let Some(m) = e.downcast_ref::<MissingArtifacts>() {
+            missing.extend(m.0.iter().cloned());
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        return Err(MissingArtifacts(missing).into());
+    }
+    Ok((a?, b?, c?))
+}
+
+fn artifact_classpath(artifacts: &[Artifact]) -> String {
+    artifacts
+        .iter()
+        .map(|a| a.path.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Join classpath fragments with ':', skipping empty ones (an empty entry
+/// would put the working directory on the JVM classpath).
+pub fn join_classpath(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .filter(|p| !p.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Convert a user dependency string to a full Maven coordinate.
+/// "org::name:v" gets the Scala binary version suffix (`_3`, or `_2.13` with
+/// `cross = "2.13"`), and "org:::name:v" the full Scala version, e.g. `_3.6.4`.
+fn resolve_dep_coord(dep: &str, cross: Option<&str>, scala_version: &str) -> Result<String> {
+    if let Some((org, rest)) = dep.split_once(":::") {
+        let Some((name, version)) = rest.split_once(':') else {
+            bail!("invalid dependency '{dep}': expected org:::name:version");
+        };
+        return Ok(format!("{org}:{name}_{scala_version}:{version}"));
+    }
+    if let Some((org, rest)) = dep.split_once("::")
+        && let Some((name, version)) = rest.split_once(':')
+    {
+        let suffix = cross.unwrap_or("3");
+        return Ok(format!("{org}:{name}_{suffix}:{version}"));
+    }
+    Ok(dep.to_string())
 }
@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::asm;
+use crate::cache;
+use crate::config::SbConfig;
+
+/// Copy `src/main/resources` to `.sb/resources`, where it goes on the
+/// classpath and into JARs. Only files whose hash changed since the last copy
+/// are written, and files removed from the sources are removed from the copy.
+pub fn sync(config: &SbConfig, project_root: &Path) -> Result<()> {
+    let src_dir = SbConfig::resource_dir(project_root);
+    let out_dir = SbConfig::resource_output_dir(project_root);
+
+    let mut new_hashes = if src_dir.is_dir() {
+        cache::hash_files_per_file(&src_dir)?
+    } else {
+        HashMap::new()
+    };
+    // Filtered files also depend on the values substituted into them
+    let values = filter_values(config);
+    let values_hash = cache::hash_strings(&values.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>());
+    for (rel, hash) in new_hashes.iter_mut() {
+        if is_filtered(config, rel) {
+            *hash ^= values_hash;
+        }
+    }
+
+    let old_hashes: HashMap<String, u64> = match cache::read_cache(project_root, "resource-hashes.json") {
+        Some(json) if out_dir.is_dir() => serde_json::from_str(&json).unwrap_or_default(),
+        _ => HashMap::new(),
+    };
+    let (changed, added, deleted) = cache::diff_hashes(&old_hashes, &new_hashes);
+
+    std::fs::create_dir_all(&out_dir)?;
+    for rel in &deleted {
+        let path = out_dir.join(rel);
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        // Directories left empty
+        for dir in path.ancestors().skip(1).take_while(|dir| *dir != out_dir) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+    for rel in changed.iter().chain(&added) {
+        let source = src_dir.join(rel);
+        let target = out_dir.join(rel);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if is_filtered(config, rel) {
+            let text = std::fs::read_to_string(&source)
+                .with_context(|| format!("failed to read {} for filtering", source.display()))?;
+            std::fs::write(&target, substitute(&text, &values))?;
+        } else {
+            std::fs::copy(&source, &target)?;
+        }
+    }
+
+    let count = changed.len() + added.len();
+    if count > 0 {
+        eprintln!("Copied {count} resource{}", if count == 1 { "" } else { "s" });
+    }
+    if count > 0 || !deleted.is_empty() {
+        cache::write_cache(project_root, "resource-hashes.json", &serde_json::to_string(&new_hashes)?)?;
+    }
+    Ok(())
+}
+
+fn is_filtered(config: &SbConfig, rel: &str) -> bool {
+    config.resources.filter.iter().any(|pattern| asm::glob_matches(pattern, rel))
+}
+
+/// The `${...}` placeholders filtering replaces, and their values.
+fn filter_values(config: &SbConfig) -> Vec<(&'static str, String)> {
+    let project = &config.project;
+    let mut values = vec![
+        ("project.name", project.name.clone()),
+        ("project.version", project.version.clone()),
+        ("project.scala-version", project.scala_version.clone()),
+    ];
+    if let Some(group_id) = &project.group_id {
+        values.push(("project.group-id", group_id.clone()));
+    }
+    values
+}
+
+/// Replace each known `${name}` in `text`. Other placeholders, such as HOCON
+/// substitutions, are left alone.
+fn substitute(text: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after
+            .find('}')
+            .and_then(|end| values.iter().find(|(name, _)| *name == &after[..end]).map(|(_, v)| (end, v)));
+        match value {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
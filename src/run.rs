@@ -1,53 +1,207 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::Path;
+use std::process::Command;
 
 use crate::compile;
 use crate::config::SbConfig;
+use crate::resolve::{self, ResolvedClasspath};
+use crate::tasty::deps::IncrementalState;
 
-pub fn run(config: &SbConfig, project_root: &Path, args: &[String]) -> Result<()> {
+pub fn run(config: &SbConfig, project_root: &Path, main: Option<&str>, args: &[String]) -> Result<()> {
     let result = compile::compile(config, project_root)?;
-
-    let main_class = config
-        .project
-        .main_class
-        .as_deref()
-        .unwrap_or_else(|| {
-            // Default: project name capitalized
-            // But we'll just require it for now
-            ""
-        });
-
-    if main_class.is_empty() {
-        bail!("no main-class specified in sb.toml");
-    }
-
-    let classes_dir = SbConfig::classes_dir(project_root);
-    let runtime_cp = format!("{}:{}", classes_dir.display(), result.resolved.user_cp);
+    let main_class = main_class(config, project_root, &result.resolved, main)?;
+    let mut cmd = command(config, project_root, &result.resolved, &main_class, args);
 
     // Use exec to replace process on Unix
     #[cfg(unix)]
     {
         use std::os::unix::process::CommandExt;
-        let err = std::process::Command::new("java")
-            .arg("--sun-misc-unsafe-memory-access=allow")
-            .arg("-cp")
-            .arg(&runtime_cp)
-            .arg(main_class)
-            .args(args)
-            .exec();
+        let err = cmd.exec();
         // exec only returns on error
         bail!("failed to exec java: {err}");
     }
 
     #[cfg(not(unix))]
     {
-        let status = std::process::Command::new("java")
-            .arg("--sun-misc-unsafe-memory-access=allow")
-            .arg("-cp")
-            .arg(&runtime_cp)
-            .arg(main_class)
-            .args(args)
-            .status()?;
+        let status = cmd.status()?;
         std::process::exit(status.code().unwrap_or(1));
     }
 }
+
+/// The `java` invocation that runs `main_class` of an already compiled project.
+pub fn command(
+    config: &SbConfig,
+    project_root: &Path,
+    resolved: &ResolvedClasspath,
+    main_class: &str,
+    args: &[String],
+) -> Command {
+    let runtime_cp = runtime_classpath(config, project_root, resolved);
+
+    let mut cmd = Command::new("java");
+    cmd.arg("--sun-misc-unsafe-memory-access=allow")
+        .arg("-cp")
+        .arg(&runtime_cp)
+        .arg(main_class)
+        .args(args);
+    cmd
+}
+
+/// The class to run in a compiled project: `requested` (from `--main`), else
+/// `main-class` from sb.toml, else the project's only entry point. With
+/// several entry points, the user picks one.
+pub fn main_class(
+    config: &SbConfig,
+    project_root: &Path,
+    resolved: &ResolvedClasspath,
+    requested: Option<&str>,
+) -> Result<String> {
+    let entry_points = entry_points(project_root);
+    let names: Vec<&str> = entry_points.iter().map(|(name, _)| name.as_str()).collect();
+
+    if let Some(requested) = requested {
+        if names.contains(&requested) {
+            return Ok(requested.to_string());
+        }
+        // A simple name is enough when it is unambiguous
+        let by_simple_name: Vec<&str> = names.iter().copied().filter(|n| simple_name(n) == requested).collect();
+        if let [name] = by_simple_name.as_slice() {
+            return Ok(name.to_string());
+        }
+        // A Java class or one from a dependency is not found in TASTy
+        if has_class(&runtime_classpath(config, project_root, resolved), requested) {
+            return Ok(requested.to_string());
+        }
+        match suggestion(requested, &names) {
+            Some(close) => bail!("no entry point named '{requested}'; did you mean '{close}'?"),
+            None if names.is_empty() => bail!("no entry point named '{requested}'; the project has none"),
+            None => bail!("no entry point named '{requested}'; available:\n{}", list(&entry_points)),
+        }
+    }
+
+    if let Some(configured) = &config.project.main_class {
+        let known = names.contains(&configured.as_str());
+        if let Some(close) = suggestion(configured, &names).filter(|_| !known) {
+            eprintln!("warning: main-class '{configured}' in sb.toml is not an entry point; did you mean '{close}'?");
+        }
+        return Ok(configured.clone());
+    }
+
+    match names.as_slice() {
+        [] => bail!("no main-class specified in sb.toml, and no @main method or object with a main method was found"),
+        [only] => Ok(only.to_string()),
+        _ if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() => choose(&entry_points),
+        _ => bail!(
+            "several entry points found; pass --main <name> or set main-class in sb.toml:\n{}",
+            list(&entry_points)
+        ),
+    }
+}
+
+/// Whether a directory or JAR on `classpath` has the class file of `class`.
+fn has_class(classpath: &str, class: &str) -> bool {
+    let file = format!("{}.class", class.replace('.', "/"));
+    classpath.split(':').filter(|entry| !entry.is_empty()).any(|entry| {
+        let path = Path::new(entry);
+        if path.is_dir() {
+            return path.join(&file).is_file();
+        }
+        std::fs::File::open(path)
+            .ok()
+            .and_then(|jar| zip::ZipArchive::new(jar).ok())
+            .is_some_and(|mut jar| jar.by_name(&file).is_ok())
+    })
+}
+
+/// Entry points found in the project's TASTy files by the last compilation,
+/// sorted by name, with the source file defining each.
+fn entry_points(project_root: &Path) -> Vec<(String, String)> {
+    let Some(state) = IncrementalState::load(project_root) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(String, String)> = state
+        .tasty_files
+        .iter()
+        .flat_map(|info| info.entry_points.iter().map(|name| (name.clone(), info.source_file.clone())))
+        .collect();
+    found.sort();
+    // An @main method and the class generated for it have the same name
+    found.dedup_by(|a, b| a.0 == b.0);
+    found
+}
+
+fn list(entry_points: &[(String, String)]) -> String {
+    entry_points
+        .iter()
+        .enumerate()
+        .map(|(i, (name, source))| format!("  {}) {name} ({source})", i + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Ask on the terminal which entry point to run.
+fn choose(entry_points: &[(String, String)]) -> Result<String> {
+    eprintln!("Several entry points found:\n{}", list(entry_points));
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("Run which one? [1-{}]: ", entry_points.len());
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).context("failed to read the choice")? == 0 {
+            bail!("no entry point chosen");
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=entry_points.len()).contains(&n) => return Ok(entry_points[n - 1].0.clone()),
+            _ => eprintln!("Enter a number between 1 and {}", entry_points.len()),
+        }
+    }
+}
+
+fn simple_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// The entry point closest to a misspelled `name`, by full or simple name.
+fn suggestion<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max = (name.chars().count() / 3).max(2);
+    candidates
+        .iter()
+        .map(|&c| {
+            let full = edit_distance(&name.to_lowercase(), &c.to_lowercase());
+            let simple = edit_distance(&name.to_lowercase(), &simple_name(c).to_lowercase());
+            (full.min(simple), c)
+        })
+        .filter(|&(distance, _)| distance <= max)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between `a` and `b`, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The project's classes and resources, those of upstream workspace members,
+/// and the runtime dependencies.
+pub fn runtime_classpath(config: &SbConfig, project_root: &Path, resolved: &ResolvedClasspath) -> String {
+    let classes_dir = SbConfig::classes_dir(project_root);
+    let resource_dir = SbConfig::resource_output_dir(project_root);
+    resolve::join_classpath(&[
+        &classes_dir.display().to_string(),
+        &resource_dir.display().to_string(),
+        &config.upstream_classpath(),
+        &resolved.run_classpath(),
+    ])
+}
@@ -0,0 +1,78 @@
+package sb
+
+import java.io.{BufferedReader, InputStreamReader, PrintStream}
+import java.net.{StandardProtocolFamily, UnixDomainSocketAddress}
+import java.nio.channels.{Channels, ServerSocketChannel, SocketChannel}
+import java.nio.charset.StandardCharsets.UTF_8
+import java.nio.file.{Files, Paths}
+import dotty.tools.dotc.reporting.ConsoleReporter
+
+/** Long-lived compiler process for one Scala version.
+  *
+  * Usage: sb.CompileServer <socket-path>
+  *
+  * Protocol (one request per connection, UTF-8 lines):
+  *   "compile", then one compiler argument per line, then an empty line.
+  *     The server streams compiler output back and ends with "\u0000sb-exit <code>".
+  *   "shutdown": the server answers "ok" and exits.
+  *
+  * Exits on its own after 30 minutes without requests.
+  */
+object CompileServer:
+  private val IdleTimeoutMillis = 30L * 60 * 1000
+  private val ExitMarker = "\u0000sb-exit "
+
+  @volatile private var lastActivity = System.currentTimeMillis()
+  @volatile private var busy = false
+
+  def main(args: Array[String]): Unit =
+    val socketPath = Paths.get(args(0))
+    Files.deleteIfExists(socketPath)
+    val server = ServerSocketChannel.open(StandardProtocolFamily.UNIX)
+    server.bind(UnixDomainSocketAddress.of(socketPath))
+    socketPath.toFile.deleteOnExit()
+    startIdleWatchdog()
+
+    while true do
+      val client = server.accept()
+      busy = true
+      try handle(client)
+      catch case e: Throwable => e.printStackTrace()
+      finally
+        client.close()
+        busy = false
+        lastActivity = System.currentTimeMillis()
+
+  private def handle(client: SocketChannel): Unit =
+    val in = BufferedReader(InputStreamReader(Channels.newInputStream(client), UTF_8))
+    val out = PrintStream(Channels.newOutputStream(client), true, UTF_8)
+    in.readLine() match
+      case "compile" =>
+        val args = Iterator.continually(in.readLine()).takeWhile(l => l != null && l.nonEmpty).toArray
+        val code =
+          try
+            val reporter = dotty.tools.dotc.Main.process(args, ConsoleReporter(Console.in, out))
+            if reporter.hasErrors then 1 else 0
+          catch
+            case e: Throwable =>
+              e.printStackTrace(out)
+              1
+        out.println(ExitMarker + code)
+        out.flush()
+      case "shutdown" =>
+        out.println("ok")
+        out.flush()
+        client.close()
+        sys.exit(0)
+      case _ =>
+        out.println(ExitMarker + 2)
+        out.flush()
+
+  private def startIdleWatchdog(): Unit =
+    val watchdog = new Thread:
+      override def run(): Unit =
+        while true do
+          Thread.sleep(60 * 1000)
+          if !busy && System.currentTimeMillis() - lastActivity > IdleTimeoutMillis then sys.exit(0)
+    watchdog.setDaemon(true)
+    watchdog.start()
@@ -0,0 +1,128 @@
+package sb
+
+import java.lang.reflect.Modifier
+import java.nio.file.{Files, Path, Paths}
+import scala.jdk.CollectionConverters.*
+import sbt.testing.*
+
+/** Runs test suites through the sbt test-interface.
+  *
+  * Usage: sb.TestRunner <test-classes-dir> [filter...]
+  *
+  * Suites are discovered by loading every class under the test classes
+  * directory and matching it against the fingerprints of each framework found
+  * on the classpath. Exits with status 1 if any test failed or errored.
+  */
+object TestRunner:
+  private val knownFrameworks = List(
+    "munit.Framework",
+    "org.scalatest.tools.Framework",
+    "utest.runner.Framework",
+    "org.scalacheck.ScalaCheckFramework",
+    "zio.test.sbt.ZTestFramework",
+    "weaver.framework.CatsEffect",
+    "org.specs2.runner.Specs2Framework",
+  )
+
+  private var passed = 0
+  private var failed = 0
+  private var errors = 0
+  private var skipped = 0
+
+  def main(args: Array[String]): Unit =
+    val classesDir = Paths.get(args(0))
+    val filters = args.drop(1).toList
+    val loader = getClass.getClassLoader
+
+    val frameworks = knownFrameworks.flatMap { name =>
+      try Some(Class.forName(name, true, loader).getDeclaredConstructor().newInstance().asInstanceOf[Framework])
+      catch case _: Throwable => None
+    }
+    if frameworks.isEmpty then
+      System.err.println("error: no supported test framework found on the test classpath")
+      sys.exit(1)
+
+    val classes = listClassNames(classesDir).flatMap { name =>
+      try Some(Class.forName(name, false, loader))
+      catch case _: Throwable => None
+    }
+
+    val claimed = scala.collection.mutable.Set.empty[String]
+    for framework <- frameworks do
+      val taskDefs = for
+        cls <- classes
+        fp <- framework.fingerprints.toList.find(matches(cls, _))
+        name = cls.getName.stripSuffix("$")
+        if !claimed.contains(name)
+        if filters.isEmpty || filters.exists(name.contains)
+      yield
+        claimed += name
+        TaskDef(name, fp, false, Array(SuiteSelector()))
+
+      if taskDefs.nonEmpty then
+        val runner = framework.runner(Array.empty, Array.empty, loader)
+        runTasks(runner.tasks(taskDefs.toArray).toList)
+        val summary = runner.done()
+        if summary != null && summary.nonEmpty then println(summary)
+
+    if claimed.isEmpty then
+      println("No test suites found.")
+    println(s"Tests: $passed passed, $failed failed, $errors errors, $skipped skipped")
+    if failed + errors > 0 then sys.exit(1)
+
+  private def runTasks(tasks: List[Task]): Unit =
+    tasks.foreach { task =>
+      runTasks(task.execute(handler, Array(logger)).toList)
+    }
+
+  private val handler: EventHandler = event =>
+    event.status match
+      case Status.Success => passed += 1
+      case Status.Failure => failed += 1
+      case Status.Error => errors += 1
+      case _ => skipped += 1
+
+  private val logger: Logger = new Logger:
+    def ansiCodesSupported(): Boolean = System.console() != null
+    def error(msg: String): Unit = println(msg)
+    def warn(msg: String): Unit = println(msg)
+    def info(msg: String): Unit = println(msg)
+    def debug(msg: String): Unit = ()
+    def trace(t: Throwable): Unit = t.printStackTrace(System.out)
+
+  private def matches(cls: Class[?], fp: Fingerprint): Boolean =
+    val isModule = cls.getName.endsWith("$")
+    fp match
+      case sub: SubclassFingerprint =>
+        sub.isModule == isModule
+          && (isModule || isConcrete(cls))
+          && superTypes(cls).contains(sub.superclassName)
+      case ann: AnnotatedFingerprint =>
+        ann.isModule == isModule
+          && (cls.getAnnotations ++ cls.getDeclaredMethods.flatMap(_.getAnnotations))
+            .exists(_.annotationType.getName == ann.annotationName)
+      case _ => false
+
+  private def isConcrete(cls: Class[?]): Boolean =
+    val mods = cls.getModifiers
+    !Modifier.isAbstract(mods) && !cls.isInterface && Modifier.isPublic(mods)
+
+  private def superTypes(cls: Class[?]): Set[String] =
+    if cls == null then Set.empty
+    else
+      val parents = Option(cls.getSuperclass).toList ++ cls.getInterfaces.toList
+      parents.flatMap(p => superTypes(p) + p.getName).toSet
+
+  private def listClassNames(dir: Path): List[String] =
+    if !Files.isDirectory(dir) then Nil
+    else
+      val stream = Files.walk(dir)
+      try
+        stream.iterator.asScala
+          .filter(_.toString.endsWith(".class"))
+          .map(p => dir.relativize(p).toString.stripSuffix(".class").replace('/', '.').replace('\\', '.'))
+          // Skip nested and anonymous classes; keep module classes (Foo$)
+          .filter(n => !n.stripSuffix("$").contains('$'))
+          .toList
+          .sorted
+      finally stream.close()
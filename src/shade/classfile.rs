@@ -0,0 +1,116 @@
+// Just enough of the class file format (JVMS §4) to rewrite the strings in
+// a class's constant pool. Everything after the pool refers to it by index
+// only, so it is copied unchanged.
+
+use anyhow::{bail, Context, Result};
+
+const MAGIC: u32 = 0xCAFE_BABE;
+
+const UTF8: u8 = 1;
+const INTEGER: u8 = 3;
+const FLOAT: u8 = 4;
+const LONG: u8 = 5;
+const DOUBLE: u8 = 6;
+const CLASS: u8 = 7;
+const STRING: u8 = 8;
+const FIELD_REF: u8 = 9;
+const METHOD_REF: u8 = 10;
+const INTERFACE_METHOD_REF: u8 = 11;
+const NAME_AND_TYPE: u8 = 12;
+const METHOD_HANDLE: u8 = 15;
+const METHOD_TYPE: u8 = 16;
+const DYNAMIC: u8 = 17;
+const INVOKE_DYNAMIC: u8 = 18;
+const MODULE: u8 = 19;
+const PACKAGE: u8 = 20;
+
+/// Copy `class` with every `CONSTANT_Utf8` entry passed through `rewrite`,
+/// which returns `None` to keep an entry as it is. Entries are modified UTF-8,
+/// so `rewrite` sees raw bytes.
+pub fn rewrite_utf8(class: &[u8], mut rewrite: impl FnMut(&[u8]) -> Option<Vec<u8>>) -> Result<Vec<u8>> {
+    let mut reader = Reader { bytes: class, pos: 0 };
+    if reader.u32()? != MAGIC {
+        bail!("not a class file");
+    }
+    reader.take(4)?; // minor and major version
+    let count = reader.u16()?;
+
+    let mut out = Vec::with_capacity(class.len() + 256);
+    out.extend_from_slice(&class[..reader.pos]);
+
+    // Indices start at 1, and longs and doubles take two slots
+    let mut index = 1;
+    while index < count {
+        let start = reader.pos;
+        let tag = reader.u8()?;
+        match tag {
+            UTF8 => {
+                let len = reader.u16()? as usize;
+                let value = reader.take(len)?;
+                match rewrite(value) {
+                    Some(new) => {
+                        let new_len = u16::try_from(new.len())
+                            .context("rewritten constant is longer than 65535 bytes")?;
+                        out.push(UTF8);
+                        out.extend_from_slice(&new_len.to_be_bytes());
+                        out.extend_from_slice(&new);
+                    }
+                    None => out.extend_from_slice(&class[start..reader.pos]),
+                }
+            }
+            CLASS | STRING | METHOD_TYPE | MODULE | PACKAGE => {
+                reader.take(2)?;
+            }
+            METHOD_HANDLE => {
+                reader.take(3)?;
+            }
+            INTEGER | FLOAT | FIELD_REF | METHOD_REF | INTERFACE_METHOD_REF | NAME_AND_TYPE | DYNAMIC
+            | INVOKE_DYNAMIC => {
+                reader.take(4)?;
+            }
+            LONG | DOUBLE => {
+                reader.take(8)?;
+                index += 1;
+            }
+            _ => bail!("unknown constant pool tag {tag} at offset {start}"),
+        }
+        if tag != UTF8 {
+            out.extend_from_slice(&class[start..reader.pos]);
+        }
+        index += 1;
+    }
+
+    out.extend_from_slice(&class[reader.pos..]);
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos + n;
+        if end > self.bytes.len() {
+            bail!("class file is truncated");
+        }
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
@@ -0,0 +1,224 @@
+pub mod classfile;
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+
+/// A `[assembly.shade]` rule, in internal form: "com/google/common/**" to
+/// "myapp/shaded/guava/@1".
+struct Rule {
+    pattern: Vec<u8>,
+    replacement: Vec<u8>,
+}
+
+/// Moves packages to new names in the classes and resources of an assembly.
+pub struct Shader {
+    /// Most specific first
+    rules: Vec<Rule>,
+}
+
+impl Shader {
+    /// Rules map a class name pattern to its replacement. In patterns, `*`
+    /// matches within a package and `**` across packages; `@1`, `@2`... in
+    /// replacements stand for what the wildcards matched.
+    pub fn new(rules: &BTreeMap<String, String>) -> Result<Self> {
+        let mut parsed = Vec::new();
+        for (pattern, replacement) in rules {
+            let valid = |s: &str, extra: &str| {
+                !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "._$".contains(c) || extra.contains(c))
+            };
+            if !valid(pattern, "*") || pattern.contains("***") {
+                bail!("invalid shade pattern \"{pattern}\": expected a class name with * or ** wildcards");
+            }
+            if !valid(replacement, "@") {
+                bail!("invalid shade replacement \"{replacement}\" for \"{pattern}\"");
+            }
+            let wildcards = pattern.replace("**", "*").matches('*').count();
+            for reference in replacement.split('@').skip(1) {
+                let digits: String = reference.chars().take_while(|c| c.is_ascii_digit()).collect();
+                match digits.parse::<usize>() {
+                    Ok(n) if (1..=wildcards).contains(&n) => {}
+                    _ => bail!(
+                        "shade replacement \"{replacement}\" refers to @{digits}, but \"{pattern}\" has {wildcards} wildcard(s)"
+                    ),
+                }
+            }
+            parsed.push(Rule {
+                pattern: pattern.replace('.', "/").into_bytes(),
+                replacement: replacement.replace('.', "/").into_bytes(),
+            });
+        }
+        // When several rules match, the longest pattern wins
+        parsed.sort_by_key(|rule| std::cmp::Reverse(rule.pattern.len()));
+        Ok(Self { rules: parsed })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Path of a JAR entry after relocation. Classes and other resources move
+    /// with their package, and service files are named after the interface.
+    pub fn entry_name(&self, name: &str) -> String {
+        if let Some(service) = name.strip_prefix("META-INF/services/") {
+            return format!("META-INF/services/{}", self.relocate_dotted(service));
+        }
+        // Multi-release JARs keep classes for newer JVMs under META-INF/versions/<n>/
+        let (prefix, path) = match name.strip_prefix("META-INF/versions/").and_then(|rest| rest.split_once('/')) {
+            Some((version, path)) => (format!("META-INF/versions/{version}/"), path),
+            None => (String::new(), name),
+        };
+        let (stem, extension) = match path.strip_suffix(".class") {
+            Some(stem) => (stem, ".class"),
+            None => (path, ""),
+        };
+        match self.relocate(stem.as_bytes()) {
+            Some(new) => format!("{prefix}{}{extension}", String::from_utf8_lossy(&new)),
+            None => name.to_string(),
+        }
+    }
+
+    /// Contents of a JAR entry after relocation: class files get their
+    /// constant pools rewritten, and service files the class names they list.
+    pub fn entry_bytes(&self, name: &str, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        if name.ends_with(".class") {
+            return classfile::rewrite_utf8(&bytes, |s| self.rewrite_constant(s))
+                .with_context(|| format!("failed to shade {name}"));
+        }
+        if name.starts_with("META-INF/services/") {
+            let text = String::from_utf8_lossy(&bytes);
+            let lines: Vec<String> = text
+                .lines()
+                .map(|line| {
+                    let class = line.split('#').next().unwrap_or_default().trim();
+                    if class.is_empty() {
+                        line.to_string()
+                    } else {
+                        line.replacen(class, &self.relocate_dotted(class), 1)
+                    }
+                })
+                .collect();
+            return Ok(format!("{}\n", lines.join("\n")).into_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// The new name of a class in internal form, e.g.
+    /// "com/google/common/base/Joiner", if a rule matches it.
+    fn relocate(&self, name: &[u8]) -> Option<Vec<u8>> {
+        self.rules.iter().find_map(|rule| {
+            let mut captures = Vec::new();
+            if !match_pattern(&rule.pattern, name, &mut captures) {
+                return None;
+            }
+            Some(expand(&rule.replacement, &captures))
+        })
+    }
+
+    /// `relocate` for a name in source form, e.g. "com.google.common.base.Joiner".
+    fn relocate_dotted(&self, name: &str) -> String {
+        match self.relocate(name.replace('.', "/").as_bytes()) {
+            Some(new) => String::from_utf8_lossy(&new).replace('/', "."),
+            None => name.to_string(),
+        }
+    }
+
+    /// A constant pool string after relocation. Besides class names, in
+    /// internal form or dotted for reflection, this covers descriptors and
+    /// generic signatures, where names appear as `L<name>;` or `L<name><`.
+    /// Other strings, such as messages in string literals, are left alone.
+    fn rewrite_constant(&self, s: &[u8]) -> Option<Vec<u8>> {
+        if is_class_name(s, b'/')
+            && let Some(new) = self.relocate(s)
+        {
+            return Some(new);
+        }
+        if is_class_name(s, b'.') {
+            let internal: Vec<u8> = s.iter().map(|&b| if b == b'.' { b'/' } else { b }).collect();
+            if let Some(new) = self.relocate(&internal) {
+                return Some(new.into_iter().map(|b| if b == b'/' { b'.' } else { b }).collect());
+            }
+        }
+
+        let mut out = Vec::with_capacity(s.len());
+        let mut changed = false;
+        let mut i = 0;
+        while i < s.len() {
+            // A class type starts a descriptor or signature, or follows one of these
+            let at_type = i == 0 || b"()[;<:^".contains(&s[i - 1]);
+            if s[i] == b'L' && at_type {
+                let end = s[i + 1..].iter().position(|&b| b == b';' || b == b'<').map(|n| i + 1 + n);
+                let name = end.map(|end| &s[i + 1..end]).filter(|name| is_class_name(name, b'/'));
+                if let Some(new) = name.and_then(|name| self.relocate(name)) {
+                    out.push(b'L');
+                    out.extend_from_slice(&new);
+                    i = end.expect("relocated names have an end");
+                    changed = true;
+                    continue;
+                }
+            }
+            out.push(s[i]);
+            i += 1;
+        }
+        changed.then_some(out)
+    }
+}
+
+/// Whether `s` is a binary class name with `separator` between packages:
+/// identifiers, which may also contain `$` and non-ASCII letters.
+fn is_class_name(s: &[u8], separator: u8) -> bool {
+    s.split(|&b| b == separator).all(|part| {
+        !part.is_empty()
+            && !part[0].is_ascii_digit()
+            && part.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80)
+    })
+}
+
+/// Match `name` against `pattern`, collecting what each wildcard matched.
+/// `*` matches at least one character other than '/', and `**` at least one
+/// of any character.
+fn match_pattern<'a>(pattern: &[u8], name: &'a [u8], captures: &mut Vec<&'a [u8]>) -> bool {
+    let (any_depth, rest) = match pattern {
+        [] => return name.is_empty(),
+        [b'*', b'*', rest @ ..] => (true, rest),
+        [b'*', rest @ ..] => (false, rest),
+        [c, rest @ ..] => {
+            return name.first() == Some(c) && match_pattern(rest, &name[1..], captures);
+        }
+    };
+    let longest = if any_depth {
+        name.len()
+    } else {
+        name.iter().position(|&b| b == b'/').unwrap_or(name.len())
+    };
+    for end in (1..=longest).rev() {
+        captures.push(&name[..end]);
+        if match_pattern(rest, &name[end..], captures) {
+            return true;
+        }
+        captures.pop();
+    }
+    false
+}
+
+/// Replace `@1`, `@2`... in `replacement` with the captured text.
+fn expand(replacement: &[u8], captures: &[&[u8]]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < replacement.len() {
+        if replacement[i] == b'@' {
+            let digits = replacement[i + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+            let n: usize = std::str::from_utf8(&replacement[i + 1..i + 1 + digits])
+                .ok()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(0);
+            if let Some(capture) = n.checked_sub(1).and_then(|n| captures.get(n)) {
+                out.extend_from_slice(capture);
+            }
+            i += 1 + digits;
+        } else {
+            out.push(replacement[i]);
+            i += 1;
+        }
+    }
+    out
+}
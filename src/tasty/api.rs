@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::Xxh3;
+
+use super::format;
+use super::names::NameTable;
+use super::trees::{NodeId, TreeArena, TreeNode};
+
+/// The public API of a TASTy file, hashed name by name so that a change only
+/// reaches the files that use the changed names.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Api {
+    /// Hash of each class's shape, by fully qualified name: its modifiers,
+    /// type and constructor parameters, parents and self type, and its
+    /// implicit and given members, which are used without being named
+    pub classes: HashMap<String, u64>,
+    /// Hash of each public member's signature, by fully qualified name (e.g.
+    /// "com.example.Utils.greet"). Overloads share one entry.
+    pub members: HashMap<String, u64>,
+}
+
+/// Names whose hash differs between two versions of an API, including names
+/// only one of them has.
+#[derive(Debug, Default)]
+pub struct ApiChanges {
+    pub classes: HashSet<String>,
+    pub members: HashSet<String>,
+}
+
+impl Api {
+    /// Merge the API of another TASTy file of the same source.
+    pub fn extend(&mut self, other: &Api) {
+        self.classes.extend(other.classes.iter().map(|(k, v)| (k.clone(), *v)));
+        self.members.extend(other.members.iter().map(|(k, v)| (k.clone(), *v)));
+    }
+
+    pub fn changes(&self, new: &Api) -> ApiChanges {
+        ApiChanges {
+            classes: changed_keys(&self.classes, &new.classes),
+            members: changed_keys(&self.members, &new.members),
+        }
+    }
+
+    /// One hash for the whole API, independent of map order.
+    pub fn hash(&self) -> u64 {
+        let mut entries: Vec<(&String, &u64)> = self.classes.iter().chain(&self.members).collect();
+        entries.sort();
+        let mut hasher = Xxh3::new();
+        for (name, hash) in entries {
+            hasher.update(name.as_bytes());
+            hasher.update(&hash.to_le_bytes());
+        }
+        hasher.digest()
+    }
+}
+
+impl ApiChanges {
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty() && self.members.is_empty()
+    }
+}
+
+fn changed_keys(old: &HashMap<String, u64>, new: &HashMap<String, u64>) -> HashSet<String> {
+    let mut changed: HashSet<String> = old
+        .iter()
+        .filter(|(name, hash)| new.get(*name) != Some(*hash))
+        .map(|(name, _)| name.clone())
+        .collect();
+    changed.extend(new.keys().filter(|name| !old.contains_key(*name)).cloned());
+    changed
+}
+
+/// Hash the classes and public members defined in one TASTy file. Method
+/// bodies and initializers are left out, so that changing them is not an API
+/// change, except where dependents compile them in: inline methods and macros.
+pub fn extract_api(arena: &TreeArena, names: &NameTable<'_>) -> Api {
+    let mut api = Api::default();
+    let packages = arena.top_level_packages();
+    for &package in &packages {
+        scan_package(arena.get(package), arena, names, &mut api);
+    }
+
+    // What a macro expands to depends on its implementation, which may be any
+    // code in the file, so there every change is a change to all its classes
+    if arena.nodes.iter().any(|node| node.tag == format::MACRO) {
+        let mut hasher = Xxh3::new();
+        for &package in &packages {
+            hash_tree(package, arena, names, &mut hasher);
+        }
+        let file_hash = hasher.digest();
+        for hash in api.classes.values_mut() {
+            *hash = mix(*hash, file_hash);
+        }
+    }
+    api
+}
+
+fn scan_package(package: &TreeNode, arena: &TreeArena, names: &NameTable<'_>, api: &mut Api) {
+    let Some((prefix, stats)) = arena.package_clause(package, names) else {
+        return;
+    };
+    for stat in stats.iter().map(|&id| arena.get(id)) {
+        match stat.tag {
+            format::PACKAGE => scan_package(stat, arena, names, api),
+            format::TYPEDEF => scan_class(stat, &prefix, arena, names, api),
+            _ => {}
+        }
+    }
+}
+
+fn scan_class(class: &TreeNode, owner: &str, arena: &TreeArena, names: &NameTable<'_>, api: &mut Api) {
+    let Some(template) = class.children.iter().map(|&id| arena.get(id)).find(|c| c.tag == format::TEMPLATE) else {
+        return;
+    };
+    // Object classes are named "Utils$"; an object and its companion class share a name
+    let simple = class.nat.map(|n| names.display(n as usize)).unwrap_or_default();
+    let name = qualify(owner, simple.trim_end_matches('$'));
+
+    let mut shape = Xxh3::new();
+    shape.update(&[class.tag]);
+    // The modifiers of a sealed class include a `Child` annotation for each
+    // subclass, so adding or removing one changes the class and every match
+    // on it is checked for exhaustivity again
+    for &modifier in class.children.iter().filter(|&&id| arena.get(id).tag != format::TEMPLATE) {
+        hash_tree(modifier, arena, names, &mut shape);
+    }
+    // Parameters, parents and self type come before the first definition, the constructor
+    let header_len = template.children.iter().take_while(|&&id| arena.get(id).tag != format::DEFDEF).count();
+    let (header, stats) = template.children.split_at(header_len);
+    for &id in header {
+        hash_tree(id, arena, names, &mut shape);
+    }
+
+    for stat in stats.iter().map(|&id| arena.get(id)) {
+        if stat.tag == format::TYPEDEF && stat.children.iter().any(|&id| arena.get(id).tag == format::TEMPLATE) {
+            scan_class(stat, &name, arena, names, api);
+            continue;
+        }
+        if !matches!(stat.tag, format::VALDEF | format::DEFDEF | format::TYPEDEF) {
+            continue;
+        }
+        // Inline bodies are compiled into their callers, where the call is
+        // only recorded as a reference to the class, so they count as part of
+        // it. Private ones too, as public inline methods can call them.
+        let inline = arena.has_modifier(stat, &[format::INLINE, format::TRANSPARENT, format::MACRO]);
+        if !inline && arena.has_modifier(stat, &[format::PRIVATE, format::LOCAL]) {
+            continue;
+        }
+        let hash = signature_hash(stat, inline, arena, names);
+        if inline || arena.has_modifier(stat, &[format::IMPLICIT, format::GIVEN]) {
+            shape.update(&hash.to_le_bytes());
+        }
+        let member = stat.nat.map(|n| names.display(n as usize)).unwrap_or_default();
+        combine(&mut api.members, qualify(&name, &member), hash);
+    }
+    combine(&mut api.classes, name, shape.digest());
+}
+
+fn qualify(owner: &str, name: &str) -> String {
+    if owner.is_empty() {
+        name.to_string()
+    } else {
+        format!("{owner}.{name}")
+    }
+}
+
+/// Add `hash` to the entry for `name`, which overloads and companions share.
+fn combine(map: &mut HashMap<String, u64>, name: String, hash: u64) {
+    let entry = map.entry(name).or_insert(0);
+    *entry = mix(*entry, hash);
+}
+
+fn mix(a: u64, b: u64) -> u64 {
+    let mut hasher = Xxh3::new();
+    hasher.update(&a.to_le_bytes());
+    hasher.update(&b.to_le_bytes());
+    hasher.digest()
+}
+
+/// Hash a VALDEF, DEFDEF or type member, without its right-hand side unless
+/// `with_body`: DEFDEF has parameters, a result type, the body and modifiers,
+/// and VALDEF a type, the initializer and modifiers. Type members keep
+/// theirs, as the alias or bounds are the API.
+fn signature_hash(def: &TreeNode, with_body: bool, arena: &TreeArena, names: &NameTable<'_>) -> u64 {
+    let rhs = match def.tag {
+        _ if with_body => None,
+        format::DEFDEF => {
+            let params = def
+                .children
+                .iter()
+                .take_while(|&&id| {
+                    matches!(
+                        arena.get(id).tag,
+                        format::PARAM | format::TYPEPARAM | format::EMPTYCLAUSE | format::SPLITCLAUSE
+                    )
+                })
+                .count();
+            Some(params + 1)
+        }
+        format::VALDEF => Some(1),
+        _ => None,
+    }
+    .filter(|&i| def.children.get(i).is_some_and(|&id| !is_modifier(arena.get(id).tag)));
+
+    let mut hasher = Xxh3::new();
+    hasher.update(&[def.tag]);
+    for (i, &child) in def.children.iter().enumerate() {
+        if Some(i) != rhs {
+            hash_tree(child, arena, names, &mut hasher);
+        }
+    }
+    hasher.digest()
+}
+
+fn is_modifier(tag: u8) -> bool {
+    matches!(tag, format::PRIVATE..=format::LAST_CAT1_TAG)
+        || matches!(tag, format::PRIVATEqualified | format::PROTECTEDqualified | format::ANNOTATION)
+}
+
+/// Hash a tree by content. Names are hashed as text, since their indices in
+/// the name table move whenever other code changes, and addresses within the
+/// file are left out for the same reason; a reference by address is hashed
+/// by the name of the definition it points to.
+fn hash_tree(id: NodeId, arena: &TreeArena, names: &NameTable<'_>, hasher: &mut Xxh3) {
+    let node = arena.get(id);
+    hasher.update(&[node.tag]);
+    if let Some(n) = node.nat {
+        if has_name_ref(node.tag) {
+            hasher.update(names.display(n as usize).as_bytes());
+        } else if is_symbol_ref(node.tag) {
+            let target = arena.at_address(n).filter(|def| has_name_ref(def.tag)).and_then(|def| def.nat);
+            if let Some(name) = target {
+                hasher.update(names.display(name as usize).as_bytes());
+            }
+        } else if !has_address(node.tag) {
+            hasher.update(&n.to_le_bytes());
+        }
+    }
+    if let Some(n) = node.nat2 {
+        hasher.update(&n.to_le_bytes());
+    }
+    for &name in &node.binder_param_names {
+        hasher.update(names.display(name as usize).as_bytes());
+    }
+    for &child in &node.children {
+        hash_tree(child, arena, names, hasher);
+    }
+}
+
+fn has_name_ref(tag: u8) -> bool {
+    matches!(
+        tag,
+        format::TERMREFpkg
+            | format::TYPEREFpkg
+            | format::STRINGconst
+            | format::IMPORTED
+            | format::RENAMED
+            | format::IDENT
+            | format::IDENTtpt
+            | format::SELECT
+            | format::SELECTtpt
+            | format::TERMREF
+            | format::TYPEREF
+            | format::SELFDEF
+            | format::NAMEDARG
+            | format::VALDEF
+            | format::DEFDEF
+            | format::TYPEDEF
+            | format::TYPEPARAM
+            | format::PARAM
+            | format::BIND
+            | format::REFINEDtype
+            | format::TERMREFin
+            | format::TYPEREFin
+            | format::SELECTin
+    )
+}
+
+/// References to a definition in the same file, by its address.
+fn is_symbol_ref(tag: u8) -> bool {
+    matches!(
+        tag,
+        format::TERMREFdirect | format::TYPEREFdirect | format::TERMREFsymbol | format::TYPEREFsymbol
+    )
+}
+
+fn has_address(tag: u8) -> bool {
+    matches!(tag, format::RECthis | format::RETURN | format::PARAMtype)
+}
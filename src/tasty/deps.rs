@@ -4,13 +4,14 @@ use std::path::Path;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
-use xxhash_rust::xxh3::Xxh3;
 
+use super::api::{self, Api, ApiChanges};
 use super::attributes::Attributes;
+use super::entry_points;
 use super::format::{self, MAGIC};
-use super::names::NameTable;
+use super::names::{NameEntry, NameTable};
 use super::reader::TastyReader;
-use super::trees::{self, TreeArena, TreeNode};
+use super::trees::{self, NodeId, TreeArena, TreeNode};
 
 /// Information extracted from a single .tasty file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,13 +22,30 @@ pub struct TastyFileInfo {
     pub source_file: String,
     /// Hash of public API signatures
     pub api_hash: u64,
-    /// Set of referenced user class names (simple or qualified, excluding scala.*/java.*)
+    /// Public API hashed per class and member
+    pub api: Api,
+    /// Fully qualified names of the symbols referenced, outside the standard
+    /// library (e.g. "com.example.model.Person", "com.example.Greeter.greet")
     pub deps: HashSet<String>,
+    /// Fully qualified names referenced in the parents of the classes this
+    /// file defines, including anonymous ones
+    pub inherits: HashSet<String>,
+    /// Simple names of every member and type referenced, including those
+    /// whose owner is not known (e.g. "process" in `b.process()`)
+    pub used_names: HashSet<String>,
+    /// Classes with a `main` method defined in this file, e.g. "app.Main"
+    pub entry_points: Vec<String>,
 }
 
+/// Bumped when what `TastyFileInfo` records changes, so that state saved by
+/// an older sb is scanned again instead of being compared with new data.
+pub const STATE_VERSION: u32 = 5;
+
 /// Full incremental compilation state, serialized to .sb/cache/incr-state.json.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IncrementalState {
+    #[serde(default)]
+    pub version: u32,
     pub source_hashes: HashMap<String, u64>,
     pub tasty_files: Vec<TastyFileInfo>,
     pub dep_hash: String,
@@ -37,7 +55,8 @@ impl IncrementalState {
     pub fn load(project_root: &Path) -> Option<Self> {
         let path = project_root.join(".sb/cache/incr-state.json");
         let content = std::fs::read_to_string(path).ok()?;
-        serde_json::from_str(&content).ok()
+        let state: Self = serde_json::from_str(&content).ok()?;
+        (state.version == STATE_VERSION).then_some(state)
     }
 
     pub fn save(&self, project_root: &Path) -> Result<()> {
@@ -61,36 +80,64 @@ impl IncrementalState {
         // Build: source_file → set of source files that depend on it
         let mut rev_deps: HashMap<String, HashSet<String>> = HashMap::new();
         for info in &self.tasty_files {
-            for dep_class in &info.deps {
-                if let Some(dep_source) = class_to_source.get(dep_class) {
-                    if dep_source != &info.source_file {
-                        rev_deps
-                            .entry(dep_source.clone())
-                            .or_default()
-                            .insert(info.source_file.clone());
-                    }
+            for dep in &info.deps {
+                if let Some(dep_source) = defining_source(&class_to_source, dep)
+                    && dep_source != &info.source_file
+                {
+                    rev_deps
+                        .entry(dep_source.clone())
+                        .or_default()
+                        .insert(info.source_file.clone());
                 }
             }
         }
         rev_deps
     }
 
-    /// Get API hash for a source file.
-    pub fn api_hash_for_source(&self, source: &str) -> Option<u64> {
-        // A source file may produce multiple tasty files; combine their API hashes
-        let mut hasher = Xxh3::new();
-        let mut found = false;
-        for info in &self.tasty_files {
-            if info.source_file == source {
-                hasher.update(&info.api_hash.to_le_bytes());
-                found = true;
-            }
+    /// The public API of a source file, over all the TASTy files it produced.
+    pub fn api_for_source(&self, source: &str) -> Api {
+        let mut api = Api::default();
+        for info in self.tasty_files.iter().filter(|info| info.source_file == source) {
+            api.extend(&info.api);
         }
-        if found {
-            Some(hasher.digest())
-        } else {
-            None
+        api
+    }
+
+    /// Whether `source` uses any of `changes`: a changed class it refers to
+    /// at all, or a changed member it refers to by name. As the owner of a
+    /// selected member is not always known, a member also counts as used when
+    /// the file uses its simple name and refers to its owner. Subclasses are
+    /// affected by every change to their parents, since they inherit all
+    /// member names.
+    pub fn is_affected(&self, source: &str, changes: &ApiChanges) -> bool {
+        let infos: Vec<&TastyFileInfo> = self.tasty_files.iter().filter(|info| info.source_file == source).collect();
+        let uses = |name: &str| {
+            infos.iter().any(|info| {
+                info.deps
+                    .iter()
+                    .any(|dep| dep.strip_prefix(name).is_some_and(|rest| rest.is_empty() || rest.starts_with('.')))
+            })
+        };
+        let inherits = |class: &str| infos.iter().any(|info| info.inherits.contains(class));
+        let uses_name = |name: &str| infos.iter().any(|info| info.used_names.contains(name));
+
+        changes.classes.iter().any(|class| uses(class) || inherits(class))
+            || changes.members.iter().any(|member| {
+                let (owner, name) = member.rsplit_once('.').unwrap_or(("", member));
+                uses(member) || (uses_name(name) && uses(owner)) || inherits(owner)
+            })
+    }
+}
+
+/// The source of the top-level class `dep` belongs to: a reference to
+/// "a.Outer.Inner.method" depends on the file of "a.Outer".
+fn defining_source<'a>(class_to_source: &'a HashMap<String, String>, dep: &str) -> Option<&'a String> {
+    let mut name = dep;
+    loop {
+        if let Some(source) = class_to_source.get(name) {
+            return Some(source);
         }
+        name = &name[..name.rfind('.')?];
     }
 }
 
@@ -183,12 +230,16 @@ fn extract_deps(
     let source_file = extract_source_file(&attrs, &name_table, tasty_path, project_root);
 
     // Extract deps and API hash from AST
-    let (deps, api_hash) = if let Some(ref arena) = ast_arena {
-        let deps = extract_type_refs(arena, &name_table);
-        let api_hash = compute_api_hash(arena, &name_table);
-        (deps, api_hash)
+    let (deps, inherits, used_names, api, entry_points) = if let Some(ref arena) = ast_arena {
+        (
+            extract_type_refs(arena, &name_table),
+            extract_parents(arena, &name_table),
+            extract_used_names(arena, &name_table),
+            api::extract_api(arena, &name_table),
+            entry_points::find_entry_points(arena, &name_table),
+        )
     } else {
-        (HashSet::new(), 0)
+        (HashSet::new(), HashSet::new(), HashSet::new(), Api::default(), Vec::new())
     };
 
     let rel_tasty = tasty_path
@@ -200,8 +251,12 @@ fn extract_deps(
     Ok(TastyFileInfo {
         tasty_path: rel_tasty,
         source_file,
-        api_hash,
+        api_hash: api.hash(),
+        api,
         deps,
+        inherits,
+        used_names,
+        entry_points,
     })
 }
 
@@ -238,104 +293,145 @@ fn extract_source_file(
     format!("src/main/scala/{stem}.scala")
 }
 
-/// Walk all tree nodes and collect TYPEREF/TERMREF name references that look like user types.
+/// Walk all tree nodes and collect the fully qualified names of the symbols
+/// that TYPEREF/TERMREF nodes point to and that SELECT/SELECTin nodes select.
 fn extract_type_refs(arena: &TreeArena, name_table: &NameTable<'_>) -> HashSet<String> {
-    let mut deps = HashSet::new();
+    referenced_names((0..arena.nodes.len()).map(|i| NodeId(i as u32)), arena, name_table)
+}
 
-    for node in &arena.nodes {
-        match node.tag {
-            // Cat4: TERMREF(115), TYPEREF(117) — nat = name ref, child = qualifier type
-            format::TERMREF | format::TYPEREF => {
-                if let Some(name_ref) = node.nat {
-                    let name = name_table.display(name_ref as usize);
-                    if !is_stdlib_name(&name) && !name.is_empty() {
-                        // Get the simple name; we'll resolve against the qualifier
-                        deps.insert(name);
-                    }
-                }
-            }
-            // Cat5: TERMREFin(174), TYPEREFin(175), SELECTin(176) — nat = name ref
-            format::TERMREFin | format::TYPEREFin | format::SELECTin => {
-                if let Some(name_ref) = node.nat {
-                    let name = name_table.display(name_ref as usize);
-                    if !is_stdlib_name(&name) && !name.is_empty() {
-                        deps.insert(name);
-                    }
-                }
+/// The names referenced in every template's parents, which come before its
+/// first definition, the constructor.
+fn extract_parents(arena: &TreeArena, name_table: &NameTable<'_>) -> HashSet<String> {
+    let mut parents = Vec::new();
+    for template in arena.nodes.iter().filter(|node| node.tag == format::TEMPLATE) {
+        for &id in &template.children {
+            match arena.get(id).tag {
+                format::DEFDEF => break,
+                format::PARAM | format::TYPEPARAM | format::SELFDEF => {}
+                _ => parents.push(id),
             }
-            _ => {}
         }
     }
-
-    deps
-}
-
-fn is_stdlib_name(name: &str) -> bool {
-    name.starts_with("scala")
-        || name.starts_with("java")
-        || name.starts_with("dotty")
-        || name == "<init>"
-        || name == "<empty>"
-        || name == "_root_"
-        || name == "<repeated>"
+    // Every node of the parent trees
+    let mut i = 0;
+    while i < parents.len() {
+        parents.extend(arena.get(parents[i]).children.iter().copied());
+        i += 1;
+    }
+    referenced_names(parents.into_iter(), arena, name_table)
 }
 
-/// Compute a hash of the public API: walk top-level VALDEF/DEFDEF/TYPEDEF that are not PRIVATE.
-fn compute_api_hash(arena: &TreeArena, name_table: &NameTable<'_>) -> u64 {
-    let mut hasher = Xxh3::new();
-
-    // Walk all nodes; for top-level defs, hash if not private
-    for node in &arena.nodes {
-        match node.tag {
-            format::VALDEF | format::DEFDEF | format::TYPEDEF => {
-                if !has_private_modifier(node, arena) {
-                    // Hash tag + name + children structure
-                    hasher.update(&[node.tag]);
-                    if let Some(name_ref) = node.nat {
-                        let name = name_table.display(name_ref as usize);
-                        hasher.update(name.as_bytes());
-                    }
-                    // Hash the type subtree structure
-                    hash_children_structure(node, arena, &mut hasher);
-                }
+fn referenced_names(
+    ids: impl Iterator<Item = NodeId>,
+    arena: &TreeArena,
+    name_table: &NameTable<'_>,
+) -> HashSet<String> {
+    let mut deps = HashSet::new();
+    for id in ids {
+        let node = arena.get(id);
+        let name = match node.tag {
+            format::TERMREF | format::TYPEREF | format::TERMREFin | format::TYPEREFin => {
+                qualified_name(id, arena, name_table)
             }
-            _ => {}
+            // Method calls and field accesses: SELECTin names the owner's type
+            // after the qualifier, SELECT only has the qualifier
+            format::SELECTin => node.children.get(1).and_then(|&owner| selected_name(node, owner, arena, name_table)),
+            format::SELECT => node.children.first().and_then(|&qual| selected_name(node, qual, arena, name_table)),
+            _ => None,
+        };
+        if let Some(name) = name
+            && !name.is_empty()
+            && !is_stdlib_name(&name)
+        {
+            deps.insert(name);
         }
     }
+    deps
+}
 
-    hasher.digest()
+/// The simple names of the members and types a file refers to, by whatever
+/// kind of reference, for the names whose owner `referenced_names` cannot tell.
+fn extract_used_names(arena: &TreeArena, name_table: &NameTable<'_>) -> HashSet<String> {
+    arena
+        .nodes
+        .iter()
+        .filter(|node| {
+            matches!(
+                node.tag,
+                format::TERMREF
+                    | format::TYPEREF
+                    | format::TERMREFin
+                    | format::TYPEREFin
+                    | format::SELECT
+                    | format::SELECTin
+                    | format::SELECTtpt
+                    | format::IDENT
+                    | format::IDENTtpt
+            )
+        })
+        .filter_map(|node| node.nat.map(|name_ref| simple_name(name_ref, name_table)))
+        .collect()
 }
 
-fn has_private_modifier(node: &TreeNode, arena: &TreeArena) -> bool {
-    for &child_id in &node.children {
-        let child = arena.get(child_id);
-        if child.tag == format::PRIVATE {
-            return true;
+/// "owner.name" for a SELECT or SELECTin node, where `owner` is the qualifier
+/// or owner type. A qualifier only has a name when it is a stable path, such
+/// as an object (`Middle.process`) or an identifier typed by one.
+fn selected_name(select: &TreeNode, owner: NodeId, arena: &TreeArena, name_table: &NameTable<'_>) -> Option<String> {
+    let owner = match arena.get(owner).tag {
+        format::IDENT => qualified_name(*arena.get(owner).children.first()?, arena, name_table)?,
+        format::SELECT => {
+            let qual = *arena.get(owner).children.first()?;
+            selected_name(arena.get(owner), qual, arena, name_table)?
         }
-    }
-    false
+        _ => qualified_name(owner, arena, name_table)?,
+    };
+    let name = simple_name(select.nat?, name_table);
+    Some(if owner.is_empty() { name } else { format!("{owner}.{name}") })
 }
 
-fn hash_children_structure(node: &TreeNode, arena: &TreeArena, hasher: &mut Xxh3) {
-    for &child_id in &node.children {
-        let child = arena.get(child_id);
-        hasher.update(&[child.tag]);
-        if let Some(n) = child.nat {
-            hasher.update(&n.to_le_bytes());
-        }
-        // Recurse into type trees but not deep into body expressions
-        match child.tag {
-            // Type-related tags: recurse
-            format::APPLIEDtype | format::APPLIEDtpt | format::ANDtype | format::ORtype
-            | format::TYPEBOUNDS | format::TYPEBOUNDStpt | format::POLYtype
-            | format::METHODtype | format::TYPELAMBDAtype | format::REFINEDtype
-            | format::REFINEDtpt | format::BYNAMEtype | format::BYNAMEtpt
-            | format::ANNOTATEDtype | format::ANNOTATEDtpt | format::PARAM
-            | format::TYPEPARAM | format::TYPEREF | format::TERMREF | format::TYPEREFin
-            | format::TERMREFin | format::SELECTin => {
-                hash_children_structure(child, arena, hasher);
+/// A name without the signature of a method name, and without the "$" of an
+/// object class, which shares the tasty file of its companion ("Greeter$").
+fn simple_name(name_ref: u64, name_table: &NameTable<'_>) -> String {
+    let name_ref = match name_table.entries.get(name_ref as usize) {
+        Some(NameEntry::Signed(original, ..) | NameEntry::TargetSigned(original, ..)) => *original as usize,
+        _ => name_ref as usize,
+    };
+    name_table.display(name_ref).trim_end_matches('$').to_string()
+}
+
+/// The fully qualified name of the symbol a reference points to, following
+/// its qualifier chain down to a package: TYPEREF(Person, TERMREFpkg
+/// com.example.model) is "com.example.model.Person". References by address
+/// (TYPEREFdirect, TERMREFsymbol...) point into the same file and have none.
+/// Methods are named without their signature, so overloads share a name.
+fn qualified_name(id: NodeId, arena: &TreeArena, name_table: &NameTable<'_>) -> Option<String> {
+    let node = arena.get(id);
+    match node.tag {
+        // Cat2: nat = fully qualified package name
+        format::TERMREFpkg | format::TYPEREFpkg => {
+            let package = name_table.display(node.nat? as usize);
+            match package.as_str() {
+                "<empty>" | "_root_" => Some(String::new()),
+                _ => Some(package),
             }
-            _ => {}
         }
+        // nat = name ref, first child = qualifier type
+        format::TERMREF | format::TYPEREF | format::TERMREFin | format::TYPEREFin => {
+            let name = simple_name(node.nat?, name_table);
+            let prefix = qualified_name(*node.children.first()?, arena, name_table)?;
+            Some(if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}.{name}")
+            })
+        }
+        // `this` of a package or class: the name of that package or class
+        format::THIS => qualified_name(*node.children.first()?, arena, name_table),
+        _ => None,
     }
 }
+
+fn is_stdlib_name(name: &str) -> bool {
+    let root = name.split('.').next().unwrap_or(name);
+    matches!(root, "scala" | "java" | "javax" | "jdk" | "dotty")
+}
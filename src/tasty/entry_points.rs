@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use super::format;
+use super::names::NameTable;
+use super::trees::{NodeId, TreeArena, TreeNode};
+
+/// JVM class names of the programs in one TASTy file: top-level objects with
+/// `def main(args: Array[String]): Unit` or extending `App`, and `@main`
+/// methods. The compiler adds a class named after each `@main` method, with a
+/// static `main` that calls it; both the method and that class are recognized.
+pub fn find_entry_points(arena: &TreeArena, names: &NameTable<'_>) -> Vec<String> {
+    let mut found = Vec::new();
+    for package in arena.top_level_packages() {
+        scan_package(arena.get(package), arena, names, &mut found);
+    }
+    let mut seen = HashSet::new();
+    found.retain(|name| seen.insert(name.clone()));
+    found
+}
+
+fn scan_package(package: &TreeNode, arena: &TreeArena, names: &NameTable<'_>, found: &mut Vec<String>) {
+    let Some((package_name, stats)) = arena.package_clause(package, names) else {
+        return;
+    };
+    let prefix = if package_name.is_empty() { String::new() } else { format!("{package_name}.") };
+
+    for stat in stats.iter().map(|&id| arena.get(id)) {
+        match stat.tag {
+            format::PACKAGE => scan_package(stat, arena, names, found),
+            format::TYPEDEF => {
+                let Some(name) = stat.nat.map(|n| names.display(n as usize)) else {
+                    continue;
+                };
+                let is_object = arena.has_modifier(stat, &[format::OBJECT]);
+                let Some(template) = stat.children.iter().map(|&id| arena.get(id)).find(|c| c.tag == format::TEMPLATE)
+                else {
+                    continue;
+                };
+                if is_object && extends_app(template, arena, names) {
+                    found.push(format!("{prefix}{}", name.trim_end_matches('$')));
+                }
+                for def in template.children.iter().map(|&id| arena.get(id)) {
+                    if def.tag != format::DEFDEF {
+                        continue;
+                    }
+                    let def_name = def.nat.map(|n| names.display(n as usize)).unwrap_or_default();
+                    if def_name == "main"
+                        && (is_object || arena.has_modifier(def, &[format::STATIC]))
+                        && is_main_signature(def, arena, names)
+                    {
+                        found.push(format!("{prefix}{}", name.trim_end_matches('$')));
+                    } else if is_object && is_annotated_main(def, arena, names) {
+                        found.push(format!("{prefix}{def_name}"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `scala.App`, which provides `main`, is among the template's
+/// parents. They come before its first definition, the constructor.
+fn extends_app(template: &TreeNode, arena: &TreeArena, names: &NameTable<'_>) -> bool {
+    template
+        .children
+        .iter()
+        .take_while(|&&id| !matches!(arena.get(id).tag, format::DEFDEF | format::SELFDEF))
+        .filter(|&&id| !matches!(arena.get(id).tag, format::PARAM | format::TYPEPARAM))
+        .any(|&id| type_names(Some(id), arena, names).iter().any(|n| n == "App"))
+}
+
+/// A single parameter of type `Array[String]` and a `Unit` result.
+fn is_main_signature(def: &TreeNode, arena: &TreeArena, names: &NameTable<'_>) -> bool {
+    let params: Vec<&TreeNode> = def
+        .children
+        .iter()
+        .map(|&id| arena.get(id))
+        .take_while(|c| matches!(c.tag, format::PARAM | format::TYPEPARAM | format::EMPTYCLAUSE | format::SPLITCLAUSE))
+        .collect();
+    let [param] = params.as_slice() else {
+        return false;
+    };
+    if param.tag != format::PARAM {
+        return false;
+    }
+    let Some(&result) = def.children.get(1) else {
+        return false;
+    };
+    let param_type = type_names(param.children.first().copied(), arena, names);
+    param_type.iter().any(|n| n == "Array")
+        && param_type.iter().any(|n| n == "String")
+        && type_names(Some(result), arena, names).iter().any(|n| n == "Unit")
+}
+
+/// A method annotated with `scala.main`.
+fn is_annotated_main(def: &TreeNode, arena: &TreeArena, names: &NameTable<'_>) -> bool {
+    def.children.iter().map(|&id| arena.get(id)).any(|child| {
+        child.tag == format::ANNOTATION
+            && child
+                .children
+                .first()
+                .and_then(|&tycon| name_of(arena.get(tycon), names))
+                .is_some_and(|name| name == "main")
+    })
+}
+
+/// The names referred to in a type tree.
+fn type_names(id: Option<NodeId>, arena: &TreeArena, names: &NameTable<'_>) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut stack: Vec<NodeId> = id.into_iter().collect();
+    while let Some(id) = stack.pop() {
+        let node = arena.get(id);
+        out.extend(name_of(node, names));
+        stack.extend(node.children.iter().copied());
+    }
+    out
+}
+
+/// The name of a reference node. Other nodes, including references by
+/// address such as TYPEREFdirect, have none.
+fn name_of(node: &TreeNode, names: &NameTable<'_>) -> Option<String> {
+    match node.tag {
+        format::IDENTtpt | format::SELECTtpt | format::TYPEREF | format::TYPEREFpkg | format::TYPEREFin => {
+            node.nat.map(|n| names.display(n as usize))
+        }
+        _ => None,
+    }
+}
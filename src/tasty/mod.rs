@@ -1,5 +1,7 @@
+pub mod api;
 pub mod attributes;
 pub mod deps;
+pub mod entry_points;
 #[allow(non_upper_case_globals)]
 pub mod format;
 pub mod names;
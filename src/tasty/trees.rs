@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use anyhow::{bail, Result};
 
 use super::format::{self, ast_category, SHAREDterm, SHAREDtype};
+use super::names::NameTable;
 use super::reader::TastyReader;
 
 /// Index into the tree arena.
@@ -27,6 +28,9 @@ pub struct TreeNode {
 /// Flat arena of tree nodes.
 pub struct TreeArena {
     pub nodes: Vec<TreeNode>,
+    /// Node starting at each address (offset in the ASTs section), for
+    /// references by address such as TYPEREFdirect.
+    pub addresses: HashMap<u32, NodeId>,
 }
 
 impl TreeArena {
@@ -39,6 +43,40 @@ impl TreeArena {
     pub fn get(&self, id: NodeId) -> &TreeNode {
         &self.nodes[id.0 as usize]
     }
+
+    pub fn at_address(&self, addr: u64) -> Option<&TreeNode> {
+        self.addresses.get(&u32::try_from(addr).ok()?).map(|&id| self.get(id))
+    }
+
+    /// The outermost package clauses, which no other node refers to.
+    pub fn top_level_packages(&self) -> Vec<NodeId> {
+        let mut referenced = vec![false; self.nodes.len()];
+        for node in &self.nodes {
+            for child in &node.children {
+                referenced[child.0 as usize] = true;
+            }
+        }
+        (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].tag == format::PACKAGE && !referenced[i])
+            .map(|i| NodeId(i as u32))
+            .collect()
+    }
+
+    /// The package a package clause is for, empty for the empty package, and
+    /// its statements. Clauses may nest; the inner package name is fully qualified.
+    pub fn package_clause<'a>(&self, package: &'a TreeNode, names: &NameTable<'_>) -> Option<(String, &'a [NodeId])> {
+        let (path, stats) = package.children.split_first()?;
+        let name = match self.get(*path).nat.map(|n| names.display(n as usize)) {
+            Some(pkg) if pkg != "<empty>" => pkg,
+            _ => String::new(),
+        };
+        Some((name, stats))
+    }
+
+    /// Whether one of a definition's children, such as a modifier, has one of `tags`.
+    pub fn has_modifier(&self, node: &TreeNode, tags: &[u8]) -> bool {
+        node.children.iter().any(|&id| tags.contains(&self.get(id).tag))
+    }
 }
 
 /// Returns the number of leading reference nats in a cat5 node payload.
@@ -65,13 +103,14 @@ fn is_binder(tag: u8) -> bool {
 /// Parse the AST section from a TASTy file.
 pub fn parse_trees(reader: &mut TastyReader<'_>) -> Result<TreeArena> {
     let base = reader.pos();
-    let mut arena = TreeArena { nodes: Vec::new() };
+    let mut arena = TreeArena { nodes: Vec::new(), addresses: HashMap::new() };
     let mut shared: HashMap<u32, NodeId> = HashMap::new();
 
     while !reader.at_end() {
         parse_tree(reader, base, &mut arena, &mut shared)?;
     }
 
+    arena.addresses = shared;
     Ok(arena)
 }
 
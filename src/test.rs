@@ -0,0 +1,53 @@
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::process::Command;
+
+use crate::bootstrap;
+use crate::compile;
+use crate::config::SbConfig;
+use crate::resolve;
+
+pub fn test(config: &SbConfig, project_root: &Path, filters: &[String]) -> Result<()> {
+    if config.project.test_dependencies.is_empty() {
+        bail!(
+            "no test-dependencies specified in sb.toml (e.g. test-dependencies = [\"org.scalameta::munit:1.0.0\"])"
+        );
+    }
+
+    let result = compile::compile(config, project_root)?;
+    let resolved = &result.resolved;
+    compile::compile_tests(config, project_root, resolved)?;
+
+    let runner_dir = bootstrap::ensure_compiled(
+        &bootstrap::TEST_RUNNER,
+        &config.project.scala_version,
+        &resolved.compiler_cp,
+        &resolve::join_classpath(&[&resolved.main_classpath(), &resolved.test_cp]),
+    )?;
+
+    let classes_dir = SbConfig::classes_dir(project_root);
+    let test_classes_dir = SbConfig::test_classes_dir(project_root);
+    let runtime_cp = resolve::join_classpath(&[
+        &test_classes_dir.display().to_string(),
+        &classes_dir.display().to_string(),
+        &SbConfig::resource_output_dir(project_root).display().to_string(),
+        &config.upstream_classpath(),
+        &resolved.main_classpath(),
+        &resolved.test_cp,
+        &runner_dir.display().to_string(),
+    ]);
+
+    let status = Command::new("java")
+        .arg("--sun-misc-unsafe-memory-access=allow")
+        .arg("-cp")
+        .arg(&runtime_cp)
+        .arg("sb.TestRunner")
+        .arg(&test_classes_dir)
+        .args(filters)
+        .current_dir(project_root)
+        .status()?;
+    if !status.success() {
+        bail!("tests failed");
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config::SbConfig;
+
+/// The changes a build makes to `.sb/classes`, undone if the build fails so
+/// that the classes of the last successful build stay intact. Removed files
+/// are moved to `.sb/backup/files` instead of being deleted, and each compiler
+/// run writes to `.sb/staging`, which is moved in only on success. The files
+/// moved in are listed in `.sb/backup/installed`, so that they can also be
+/// removed when sb was killed before it could roll back.
+pub struct Transaction {
+    classes_dir: PathBuf,
+    backup_dir: PathBuf,
+    staging_dir: PathBuf,
+    /// Files moved in from staging, relative to the classes dir
+    installed: HashSet<PathBuf>,
+}
+
+impl Transaction {
+    /// Start a transaction, first rolling back one that sb was killed in the
+    /// middle of.
+    pub fn begin(project_root: &Path) -> Result<Self> {
+        let mut tx = Transaction {
+            classes_dir: SbConfig::classes_dir(project_root),
+            backup_dir: project_root.join(".sb/backup"),
+            staging_dir: project_root.join(".sb/staging"),
+            installed: HashSet::new(),
+        };
+        if tx.backup_dir.exists() {
+            if let Ok(manifest) = std::fs::read_to_string(tx.manifest()) {
+                tx.installed = manifest.lines().map(PathBuf::from).collect();
+            }
+            tx.undo()?;
+        }
+        if tx.staging_dir.exists() {
+            std::fs::remove_dir_all(&tx.staging_dir)?;
+        }
+        Ok(tx)
+    }
+
+    /// An empty directory for the compiler's output.
+    pub fn staging_dir(&self) -> Result<&Path> {
+        if self.staging_dir.exists() {
+            std::fs::remove_dir_all(&self.staging_dir)?;
+        }
+        std::fs::create_dir_all(&self.staging_dir)?;
+        Ok(&self.staging_dir)
+    }
+
+    /// Remove a file from the classes dir, keeping the original for rollback.
+    pub fn remove(&mut self, path: &Path) -> Result<()> {
+        let Ok(rel) = path.strip_prefix(&self.classes_dir) else {
+            return Ok(());
+        };
+        if !path.is_file() {
+            return Ok(());
+        }
+        let backup = self.files_dir().join(rel);
+        // Only the first version of a file is the original; later ones came from staging
+        if self.installed.contains(rel) || backup.exists() {
+            std::fs::remove_file(path)?;
+        } else {
+            if let Some(parent) = backup.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(path, &backup)
+                .with_context(|| format!("failed to back up {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Move the compiler's output from the staging dir into the classes dir.
+    pub fn install(&mut self) -> Result<()> {
+        let files: Vec<PathBuf> = WalkDir::new(&self.staging_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .collect();
+        for file in files {
+            let rel = file.strip_prefix(&self.staging_dir)?.to_path_buf();
+            let target = self.classes_dir.join(&rel);
+            self.remove(&target)?;
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Listed before it is moved, so that a crash never leaves it unlisted
+            if !self.installed.contains(&rel) {
+                std::fs::create_dir_all(&self.backup_dir)?;
+                let mut manifest = std::fs::OpenOptions::new().create(true).append(true).open(self.manifest())?;
+                writeln!(manifest, "{}", rel.display())?;
+            }
+            std::fs::rename(&file, &target)
+                .with_context(|| format!("failed to move {} into {}", file.display(), self.classes_dir.display()))?;
+            self.installed.insert(rel);
+        }
+        std::fs::remove_dir_all(&self.staging_dir)?;
+        Ok(())
+    }
+
+    /// Replace the whole classes dir with the compiler's output, for full builds.
+    pub fn replace_classes(self) -> Result<()> {
+        if self.classes_dir.exists() {
+            std::fs::remove_dir_all(&self.classes_dir)?;
+        }
+        std::fs::rename(&self.staging_dir, &self.classes_dir)
+            .with_context(|| format!("failed to move the compiler's output to {}", self.classes_dir.display()))?;
+        self.commit()
+    }
+
+    /// Keep the changes.
+    pub fn commit(self) -> Result<()> {
+        if self.backup_dir.exists() {
+            std::fs::remove_dir_all(&self.backup_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Undo the changes: remove what was installed and put back what was removed.
+    pub fn rollback(self) -> Result<()> {
+        self.undo()
+    }
+
+    fn undo(&self) -> Result<()> {
+        for rel in &self.installed {
+            let path = self.classes_dir.join(rel);
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        if self.staging_dir.exists() {
+            std::fs::remove_dir_all(&self.staging_dir)?;
+        }
+
+        let files_dir = self.files_dir();
+        let files: Vec<PathBuf> = WalkDir::new(&files_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .collect();
+        for file in files {
+            let target = self.classes_dir.join(file.strip_prefix(&files_dir)?);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&file, &target)
+                .with_context(|| format!("failed to restore {}", target.display()))?;
+        }
+        if self.backup_dir.exists() {
+            std::fs::remove_dir_all(&self.backup_dir)?;
+        }
+        Ok(())
+    }
+
+    fn files_dir(&self) -> PathBuf {
+        self.backup_dir.join("files")
+    }
+
+    fn manifest(&self) -> PathBuf {
+        self.backup_dir.join("installed")
+    }
+}
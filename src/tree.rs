@@ -0,0 +1,202 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::config::SbConfig;
+use crate::lock::{self, Scope};
+use crate::maven::coord::MavenCoord;
+use crate::maven::resolve::{Edge, Eviction, Resolution, ROOT_REQUESTER};
+use crate::resolve;
+
+#[derive(Serialize)]
+struct TreeJson<'a> {
+    name: &'a str,
+    version: &'a str,
+    scopes: Vec<ScopeJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct ScopeJson<'a> {
+    scope: Scope,
+    dependencies: &'a [Edge],
+    evictions: &'a [Eviction],
+}
+
+#[derive(Serialize)]
+struct WhyJson<'a> {
+    scope: Scope,
+    module: &'a str,
+    version: &'a str,
+    /// Each path starts at a dependency declared in sb.toml and ends at the module
+    paths: Vec<Vec<&'a Edge>>,
+}
+
+/// `sb tree`: print the transitive dependencies of each scope.
+pub fn tree(config: &SbConfig, project_root: &Path, json: bool) -> Result<()> {
+    let graphs = resolve::graph(config, project_root)?;
+
+    if json {
+        let out = TreeJson {
+            name: &config.project.name,
+            version: &config.project.version,
+            scopes: graphs
+                .iter()
+                .map(|(scope, r)| ScopeJson {
+                    scope: *scope,
+                    dependencies: &r.edges,
+                    evictions: &r.evictions,
+                })
+                .collect(),
+        };
+        println!("{}", serde_json::to_string(&out)?);
+        return Ok(());
+    }
+
+    println!("{} {}", config.project.name, config.project.version);
+    for (scope, resolution) in &graphs {
+        if *scope == Scope::Test {
+            println!();
+            println!("[test-dependencies]");
+        }
+        let children = children_of(resolution);
+        let roots = children.get(ROOT_REQUESTER).cloned().unwrap_or_default();
+        let mut expanded = HashSet::new();
+        print_level(&roots, &children, "", &mut expanded);
+    }
+    Ok(())
+}
+
+/// `sb why <dep>`: print every path from sb.toml to the artifacts matching `dep`.
+pub fn why(config: &SbConfig, project_root: &Path, dep: &str, json: bool) -> Result<()> {
+    let graphs = resolve::graph(config, project_root)?;
+
+    let mut found = Vec::new();
+    for (scope, resolution) in &graphs {
+        let children = children_of(resolution);
+        let mut paths = Vec::new();
+        let roots = children.get(ROOT_REQUESTER).cloned().unwrap_or_default();
+        for edge in roots {
+            collect_paths(edge, dep, &children, &mut Vec::new(), &mut paths);
+        }
+        // Group by the artifact reached, since `dep` can match several
+        let mut by_module: Vec<(&str, &str, Vec<Vec<&Edge>>)> = Vec::new();
+        for path in paths {
+            let last = path.last().expect("paths are never empty");
+            let version = last.selected.as_deref().unwrap_or_default();
+            match by_module.iter_mut().find(|(m, _, _)| *m == last.module) {
+                Some((_, _, group)) => group.push(path),
+                None => by_module.push((&last.module, version, vec![path])),
+            }
+        }
+        for (module, version, paths) in by_module {
+            found.push(WhyJson { scope: *scope, module, version, paths });
+        }
+    }
+    if found.is_empty() {
+        bail!("no dependency matching '{dep}' in the dependency graph of {}", config.project.name);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string(&found)?);
+        return Ok(());
+    }
+    for (i, entry) in found.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let scope = match entry.scope {
+            Scope::Test => "test",
+            _ => "compile",
+        };
+        println!("{}:{} ({scope})", entry.module, entry.version);
+        for path in &entry.paths {
+            let labels: Vec<String> = path.iter().map(|e| label(e)).collect();
+            println!("  {}", labels.join(" > "));
+        }
+    }
+    Ok(())
+}
+
+/// Declared dependencies keyed by the coordinate that declares them.
+fn children_of(resolution: &Resolution) -> HashMap<&str, Vec<&Edge>> {
+    let mut children: HashMap<&str, Vec<&Edge>> = HashMap::new();
+    for edge in &resolution.edges {
+        children.entry(edge.from.as_str()).or_default().push(edge);
+    }
+    children
+}
+
+/// The coordinate whose POM lists this edge's own dependencies.
+fn target(edge: &Edge) -> Option<String> {
+    edge.selected.as_ref().map(|v| format!("{}:{v}", edge.module))
+}
+
+/// e.g. "g:a:1.0", "g:a:1.0 -> 1.1" when evicted, "g:a (excluded)", "g:a:1.0 (provided)".
+fn label(edge: &Edge) -> String {
+    let mut label = match &edge.selected {
+        None if edge.requested.is_empty() => format!("{} (excluded)", edge.module),
+        None => format!("{}:{} (excluded)", edge.module, edge.requested),
+        Some(v) if *v != edge.requested => format!("{}:{} -> {v}", edge.module, edge.requested),
+        Some(v) => format!("{}:{v}", edge.module),
+    };
+    if let Some(c) = &edge.classifier {
+        label.push_str(&format!(" [{c}]"));
+    }
+    if edge.scope != "compile" {
+        label.push_str(&format!(" ({})", edge.scope));
+    }
+    label
+}
+
+/// Print `edges` with box-drawing guides. Subtrees that were already printed
+/// are marked with "(*)" instead of being repeated.
+fn print_level<'a>(
+    edges: &[&'a Edge],
+    children: &HashMap<&str, Vec<&'a Edge>>,
+    prefix: &str,
+    expanded: &mut HashSet<String>,
+) {
+    for (i, edge) in edges.iter().enumerate() {
+        let last = i + 1 == edges.len();
+        let (branch, guide) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        let below = target(edge).and_then(|t| children.get(t.as_str()).map(|c| (t, c)));
+        match below {
+            Some((t, _)) if expanded.contains(&t) => {
+                println!("{prefix}{branch}{} (*)", label(edge));
+            }
+            Some((t, grandchildren)) => {
+                println!("{prefix}{branch}{}", label(edge));
+                expanded.insert(t);
+                print_level(grandchildren, children, &format!("{prefix}{guide}"), expanded);
+            }
+            None => println!("{prefix}{branch}{}", label(edge)),
+        }
+    }
+}
+
+/// Depth-first search for paths ending at an artifact matching `dep`.
+/// Excluded edges are skipped, and so are cycles.
+fn collect_paths<'a>(
+    edge: &'a Edge,
+    dep: &str,
+    children: &HashMap<&str, Vec<&'a Edge>>,
+    path: &mut Vec<&'a Edge>,
+    paths: &mut Vec<Vec<&'a Edge>>,
+) {
+    let Some(coord) = target(edge) else {
+        return;
+    };
+    if path.iter().any(|e| e.module == edge.module) {
+        return;
+    }
+    path.push(edge);
+    if MavenCoord::parse(&coord).is_ok_and(|c| lock::matches_dep(&c, dep)) {
+        paths.push(path.clone());
+    } else if let Some(next) = children.get(coord.as_str()) {
+        for child in next {
+            collect_paths(child, dep, children, path, paths);
+        }
+    }
+    path.pop();
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use crate::compile;
+use crate::config::SbConfig;
+use crate::run;
+use crate::test;
+
+/// How long the tree must be quiet before a rebuild starts.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub enum Action {
+    Build,
+    Run(Vec<String>),
+    Test(Vec<String>),
+}
+
+/// Rebuild (and run or test) every time a source file or sb.toml changes.
+/// `load` is called before each round so sb.toml edits are picked up.
+pub fn watch(
+    project_root: &Path,
+    action: &Action,
+    load: impl Fn(&Path) -> Result<SbConfig>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("failed to start file watcher")?;
+    let mut watched: Vec<(PathBuf, RecursiveMode)> = Vec::new();
+    let mut child: Option<Child> = None;
+
+    loop {
+        let config = match load(project_root) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("error: {e:#}");
+                None
+            }
+        };
+
+        // The set of watched paths can change when sb.toml gains or loses upstream
+        // members. A directory that does not exist yet is watched through its
+        // nearest existing parent until it is created.
+        let paths = watch_paths(project_root, config.as_ref(), action);
+        let missing: Vec<PathBuf> = paths.iter().filter(|(p, _)| !p.exists()).map(|(p, _)| p.clone()).collect();
+        let mut wanted: Vec<(PathBuf, RecursiveMode)> = Vec::new();
+        for (path, mode) in paths {
+            let watch = if path.exists() {
+                (path, mode)
+            } else {
+                (nearest_existing(&path), RecursiveMode::NonRecursive)
+            };
+            if !wanted.contains(&watch) {
+                wanted.push(watch);
+            }
+        }
+        for (path, _) in watched.iter().filter(|w| !wanted.contains(w)) {
+            let _ = watcher.unwatch(path);
+        }
+        for (path, mode) in wanted.iter().filter(|w| !watched.contains(w)) {
+            watcher
+                .watch(path, *mode)
+                .with_context(|| format!("failed to watch {}", path.display()))?;
+        }
+        watched = wanted;
+
+        if let Some(config) = &config {
+            match round(config, project_root, action) {
+                Ok(spawned) => child = spawned,
+                Err(e) => eprintln!("error: {e:#}"),
+            }
+        }
+
+        eprintln!("Watching for changes... (Ctrl-C to stop)");
+        wait_for_change(&rx, &missing)?;
+        if let Some(mut previous) = child.take() {
+            let _ = previous.kill();
+            let _ = previous.wait();
+        }
+    }
+}
+
+/// One build round. For `Run`, returns the still-running program.
+fn round(config: &SbConfig, project_root: &Path, action: &Action) -> Result<Option<Child>> {
+    match action {
+        Action::Build => {
+            compile::compile(config, project_root)?;
+            Ok(None)
+        }
+        Action::Run(args) => {
+            let result = compile::compile(config, project_root)?;
+            let main_class = run::main_class(config, project_root, &result.resolved, None)?;
+            let child = run::command(config, project_root, &result.resolved, &main_class, args)
+                .spawn()
+                .context("failed to start java")?;
+            Ok(Some(child))
+        }
+        Action::Test(filters) => {
+            test::test(config, project_root, filters)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Source trees are watched recursively. Project roots are watched without
+/// recursion for sb.toml, since editors often save it by replacing the file.
+fn watch_paths(
+    project_root: &Path,
+    config: Option<&SbConfig>,
+    action: &Action,
+) -> Vec<(PathBuf, RecursiveMode)> {
+    let mut roots = vec![project_root.to_path_buf()];
+    if let Some(config) = config {
+        roots.extend(config.upstream.iter().cloned());
+    }
+
+    let mut paths = Vec::new();
+    for (i, root) in roots.iter().enumerate() {
+        paths.push((root.clone(), RecursiveMode::NonRecursive));
+        paths.push((SbConfig::source_dir(root), RecursiveMode::Recursive));
+        paths.push((SbConfig::resource_dir(root), RecursiveMode::Recursive));
+        if i == 0 && matches!(action, Action::Test(_)) {
+            paths.push((SbConfig::test_source_dir(root), RecursiveMode::Recursive));
+        }
+    }
+    paths
+}
+
+fn nearest_existing(path: &Path) -> PathBuf {
+    path.ancestors().find(|dir| dir.is_dir()).unwrap_or(path).to_path_buf()
+}
+
+/// Block until a relevant change arrives, then until events stop for `DEBOUNCE`.
+/// Creating one of the `missing` directories, or a parent of one, is relevant.
+fn wait_for_change(rx: &Receiver<notify::Result<notify::Event>>, missing: &[PathBuf]) -> Result<()> {
+    loop {
+        let event = rx.recv().context("file watcher stopped")?;
+        if is_relevant(event, missing) {
+            break;
+        }
+    }
+    while rx.recv_timeout(DEBOUNCE).is_ok() {}
+    Ok(())
+}
+
+fn is_relevant(event: notify::Result<notify::Event>, missing: &[PathBuf]) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    event.paths.iter().any(|p| {
+        p.file_name().is_some_and(|n| n == "sb.toml")
+            || p.extension().is_some_and(|ext| ext == "scala")
+            || p.ancestors().any(|dir| dir.ends_with("src/main/resources"))
+            || missing.iter().any(|dir| dir.starts_with(p))
+    })
+}
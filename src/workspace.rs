@@ -0,0 +1,235 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::{BuildFlags, DependencyScope, SbConfig};
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceFile {
+    workspace: WorkspaceTable,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceTable {
+    members: Vec<String>,
+}
+
+pub struct Member {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Member config with `upstream` set and upstream dependencies merged in.
+    pub config: SbConfig,
+}
+
+/// A root `sb.toml` with a `[workspace]` table listing member directories.
+pub struct Workspace {
+    /// Members in topological order: every member comes after those it depends on.
+    pub members: Vec<Member>,
+}
+
+impl Workspace {
+    /// Load the workspace rooted at `root`, or `None` if its sb.toml is a plain project.
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join("sb.toml");
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let value: toml::Value =
+            toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+        if value.get("workspace").is_none() {
+            return Ok(None);
+        }
+        let file: WorkspaceFile =
+            toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+
+        let mut members = Vec::new();
+        let mut seen = HashSet::new();
+        for rel in &file.workspace.members {
+            let dir = root.join(rel);
+            let config = SbConfig::load(&dir)
+                .with_context(|| format!("failed to load workspace member '{rel}'"))?;
+            let name = config.project.name.clone();
+            if !seen.insert(name.clone()) {
+                bail!("duplicate workspace member name '{name}'");
+            }
+            members.push(Member { name, dir, config });
+        }
+
+        let members = topological_sort(members)?;
+        let mut ws = Workspace { members };
+        ws.link_upstream()?;
+        Ok(Some(ws))
+    }
+
+    /// Find the workspace whose members include `dir`, searching parent directories.
+    /// Returns the workspace and the member's name.
+    pub fn enclosing(dir: &Path) -> Result<Option<(Self, String)>> {
+        let dir = std::fs::canonicalize(dir)?;
+        for ancestor in dir.ancestors().skip(1) {
+            if !ancestor.join("sb.toml").exists() {
+                continue;
+            }
+            if let Some(ws) = Self::load(ancestor)? {
+                let found = ws
+                    .members
+                    .iter()
+                    .find(|m| std::fs::canonicalize(&m.dir).is_ok_and(|d| d == dir))
+                    .map(|m| m.name.clone());
+                if let Some(name) = found {
+                    return Ok(Some((ws, name)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn member(&self, name: &str) -> Result<&Member> {
+        self.members.iter().find(|m| m.name == name).ok_or_else(|| {
+            let names: Vec<&str> = self.members.iter().map(|m| m.name.as_str()).collect();
+            anyhow::anyhow!(
+                "no workspace member named '{name}' (members: {})",
+                names.join(", ")
+            )
+        })
+    }
+
+    pub fn set_flags(&mut self, flags: BuildFlags) {
+        for member in &mut self.members {
+            member.config.flags = flags;
+        }
+    }
+
+    /// Take ownership of a member's config.
+    pub fn into_member_config(self, name: &str) -> Result<SbConfig> {
+        self.member(name)?;
+        Ok(self
+            .members
+            .into_iter()
+            .find(|m| m.name == name)
+            .map(|m| m.config)
+            .expect("member exists"))
+    }
+
+    /// Members to build, in order: `target` and everything it depends on, or all members.
+    pub fn build_order(&self, target: Option<&str>) -> Result<Vec<&Member>> {
+        let Some(target) = target else {
+            return Ok(self.members.iter().collect());
+        };
+        let wanted = self.closure(self.member(target)?);
+        Ok(self
+            .members
+            .iter()
+            .filter(|m| wanted.contains(m.name.as_str()))
+            .collect())
+    }
+
+    /// Names of `member` and all of its transitive upstream members.
+    fn closure<'a>(&'a self, member: &'a Member) -> HashSet<&'a str> {
+        let mut result = HashSet::new();
+        let mut stack = vec![member];
+        while let Some(m) = stack.pop() {
+            if result.insert(m.name.as_str()) {
+                for dep in &m.config.project.depends_on {
+                    if let Ok(up) = self.member(dep) {
+                        stack.push(up);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Give each member the project roots, dependencies and repositories of its upstream members.
+    fn link_upstream(&mut self) -> Result<()> {
+        let mut links = Vec::new();
+        for member in &self.members {
+            let closure = self.closure(member);
+            let mut upstream = Vec::new();
+            let mut deps = Vec::new();
+            let mut repos = Vec::new();
+            // Walk in build order so the classpath is deterministic
+            for up in self.members.iter().filter(|m| m.name != member.name) {
+                if !closure.contains(up.name.as_str()) {
+                    continue;
+                }
+                if up.config.project.scala_version != member.config.project.scala_version {
+                    bail!(
+                        "workspace member '{}' uses Scala {} but depends on '{}' which uses Scala {}",
+                        member.name,
+                        member.config.project.scala_version,
+                        up.name,
+                        up.config.project.scala_version
+                    );
+                }
+                upstream.push(up.dir.clone());
+                // Provided dependencies are up to whoever runs the code, so they are not inherited
+                deps.extend(
+                    up.config
+                        .project
+                        .dependencies
+                        .iter()
+                        .filter(|d| d.scope != DependencyScope::Provided)
+                        .cloned(),
+                );
+                repos.extend(up.config.repositories.iter().cloned());
+            }
+            links.push((upstream, deps, repos));
+        }
+
+        for (member, (upstream, deps, repos)) in self.members.iter_mut().zip(links) {
+            let project = &mut member.config.project;
+            for dep in deps {
+                if !project.dependencies.contains(&dep) {
+                    project.dependencies.push(dep);
+                }
+            }
+            for repo in repos {
+                if !member.config.repositories.contains(&repo) {
+                    member.config.repositories.push(repo);
+                }
+            }
+            member.config.upstream = upstream;
+        }
+        Ok(())
+    }
+}
+
+/// Order members so that each comes after everything in its `depends-on`.
+fn topological_sort(members: Vec<Member>) -> Result<Vec<Member>> {
+    let names: HashSet<String> = members.iter().map(|m| m.name.clone()).collect();
+    for m in &members {
+        for dep in &m.config.project.depends_on {
+            if !names.contains(dep) {
+                bail!("workspace member '{}' depends on unknown member '{dep}'", m.name);
+            }
+        }
+    }
+
+    let mut remaining: HashMap<String, Member> =
+        members.into_iter().map(|m| (m.name.clone(), m)).collect();
+    let mut order: Vec<Member> = Vec::new();
+    while !remaining.is_empty() {
+        let mut ready: Vec<String> = remaining
+            .values()
+            .filter(|m| {
+                m.config
+                    .project
+                    .depends_on
+                    .iter()
+                    .all(|d| !remaining.contains_key(d))
+            })
+            .map(|m| m.name.clone())
+            .collect();
+        if ready.is_empty() {
+            let mut cycle: Vec<&str> = remaining.keys().map(|s| s.as_str()).collect();
+            cycle.sort();
+            bail!("dependency cycle between workspace members: {}", cycle.join(", "));
+        }
+        ready.sort();
+        for name in ready {
+            order.push(remaining.remove(&name).expect("ready member exists"));
+        }
+    }
+    Ok(order)
+}
@@ -3,8 +3,10 @@ use std::process::Command;
 use std::sync::LazyLock;
 use std::sync::Mutex;
 
-/// Integration tests must run sequentially because they share a global Maven/JAR cache.
-/// Parallel resolution of the same artifacts can cause corruption.
+/// Integration tests run sequentially because they share the compile daemon: some
+/// tests start it, assert on whether it was reused, or shut it down. (The Maven
+/// cache itself is safe for concurrent use; downloads are verified and renamed
+/// into place atomically.)
 static TEST_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
 
 fn sb_binary() -> PathBuf {
@@ -167,6 +169,1152 @@ fn asm_produces_runnable_jar() {
     );
 }
 
+#[test]
+fn test_command_runs_suites() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pos/munit_tests");
+    let output = run_sb(&project, &["test"]);
+    assert!(
+        output.status.success(),
+        "sb test failed\nstdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Tests: 2 passed, 0 failed"),
+        "expected test summary, got: {stdout}",
+    );
+}
+
+#[test]
+fn test_command_fails_on_failing_test() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pos/munit_tests");
+    let tmp = tempfile::tempdir().unwrap();
+    let work_dir = tmp.path().join("munit_tests");
+    copy_dir_all(&project, &work_dir).unwrap();
+
+    std::fs::write(
+        work_dir.join("src/test/scala/BrokenSuite.scala"),
+        "class BrokenSuite extends munit.FunSuite:\n  test(\"broken\") {\n    assertEquals(Calc.add(1, 1), 3)\n  }\n",
+    ).unwrap();
+
+    let output = run_sb_in(&work_dir, &["test"]);
+    assert!(!output.status.success(), "sb test should fail when a test fails");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("2 passed, 1 failed"),
+        "expected failure in summary, got: {stdout}",
+    );
+}
+
+fn setup_workspace() -> (tempfile::TempDir, PathBuf) {
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pos/workspace");
+    let tmp = tempfile::tempdir().unwrap();
+    let work_dir = tmp.path().join("workspace");
+    copy_dir_all(&project, &work_dir).unwrap();
+    (tmp, work_dir)
+}
+
+#[test]
+fn workspace_builds_members_in_order() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_workspace();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "workspace build failed: {stderr}");
+    let core = stderr.find("[core]").expect("core not built");
+    let app = stderr.find("[app]").expect("app not built");
+    assert!(core < app, "core must be built before app, got: {stderr}");
+    assert!(work_dir.join("core/.sb/classes").exists());
+    assert!(work_dir.join("app/.sb/classes").exists());
+}
+
+#[test]
+fn workspace_build_single_member() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_workspace();
+
+    let output = run_sb_in(&work_dir, &["build", "-p", "core"]);
+    assert!(
+        output.status.success(),
+        "build -p core failed: {}",
+        String::from_utf8_lossy(&output.stderr),
+    );
+    assert!(work_dir.join("core/.sb/classes").exists());
+    assert!(!work_dir.join("app/.sb").exists(), "app should not be built");
+}
+
+#[test]
+fn workspace_member_run_builds_upstream() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_workspace();
+
+    let output = run_sb_in(&work_dir.join("app"), &["run"]);
+    assert!(
+        output.status.success(),
+        "run in member failed: {}",
+        String::from_utf8_lossy(&output.stderr),
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Area: 12"));
+}
+
+#[test]
+fn workspace_upstream_api_change_rebuilds_downstream() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_workspace();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success());
+
+    std::fs::write(
+        work_dir.join("core/src/main/scala/Shapes.scala"),
+        "package core\n\ncase class Rect(w: Int, h: Int):\n  def area: Int = w * h\n  def perimeter: Int = 2 * (w + h)\n",
+    ).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "rebuild failed: {stderr}");
+    let app_section = &stderr[stderr.find("[app]").expect("app not built")..];
+    assert!(
+        app_section.contains("Compiling"),
+        "expected app to recompile after core API change, got: {stderr}",
+    );
+}
+
+#[test]
+fn daemon_reused_and_shut_down() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental();
+
+    // The initial build may have started a daemon; this one must compile through it
+    let top = work_dir.join("src/main/scala/Top.scala");
+    let content = std::fs::read_to_string(&top).unwrap();
+    std::fs::write(&top, format!("{content}\n// touched\n")).unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "rebuild failed: {}", stderr_of(&output));
+    assert!(
+        !stderr_of(&output).contains("Started compile daemon"),
+        "daemon was not reused: {}",
+        stderr_of(&output),
+    );
+
+    let output = run_sb_in(&work_dir, &["shutdown"]);
+    assert!(output.status.success(), "shutdown failed: {}", stderr_of(&output));
+    assert!(stderr_of(&output).contains("Stopped compile daemon"));
+
+    let output = run_sb_in(&work_dir, &["shutdown"]);
+    assert!(stderr_of(&output).contains("No compile daemons running."));
+}
+
+/// Read lines from `rx` until one contains `needle`, failing after a timeout.
+fn wait_for_line(rx: &std::sync::mpsc::Receiver<String>, needle: &str) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(120);
+    let mut seen = String::new();
+    while let Some(left) = deadline.checked_duration_since(std::time::Instant::now()) {
+        match rx.recv_timeout(left) {
+            Ok(line) if line.contains(needle) => return,
+            Ok(line) => seen.push_str(&format!("{line}\n")),
+            Err(_) => break,
+        }
+    }
+    panic!("timed out waiting for {needle:?}; output so far:\n{seen}");
+}
+
+#[test]
+fn watch_rebuilds_and_survives_errors() {
+    use std::io::BufRead;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental();
+
+    let mut child = Command::new(sb_binary())
+        .arg("watch")
+        .current_dir(&work_dir)
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("failed to run sb watch");
+    let stderr = child.stderr.take().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::BufReader::new(stderr).lines().map_while(Result::ok) {
+            let _ = tx.send(line);
+        }
+    });
+
+    wait_for_line(&rx, "Watching for changes");
+    let middle = work_dir.join("src/main/scala/Middle.scala");
+    std::fs::write(&middle, "object Middle:\n  def process(b: Base): String = oops\n").unwrap();
+    wait_for_line(&rx, "error:");
+    wait_for_line(&rx, "Watching for changes");
+
+    std::fs::write(
+        &middle,
+        "object Middle:\n  def process(b: Base): String = s\"Value: ${b.x}\"\n",
+    )
+    .unwrap();
+    wait_for_line(&rx, "Compiling 1 source file...");
+    wait_for_line(&rx, "Watching for changes");
+
+    // A resources directory created after the watch started is watched too
+    let resources = work_dir.join("src/main/resources");
+    std::fs::create_dir_all(&resources).unwrap();
+    wait_for_line(&rx, "Watching for changes");
+    std::fs::write(resources.join("app.conf"), "greeting = hello\n").unwrap();
+    wait_for_line(&rx, "Watching for changes");
+    assert!(work_dir.join(".sb/resources/app.conf").exists(), "app.conf was not copied");
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn setup_project(name: &str) -> (tempfile::TempDir, PathBuf) {
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pos").join(name);
+    let tmp = tempfile::tempdir().unwrap();
+    let work_dir = tmp.path().join(name);
+    copy_dir_all(&project, &work_dir).unwrap();
+    (tmp, work_dir)
+}
+
+#[test]
+fn lockfile_written_and_enforced() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).expect("sb.lock written");
+    assert!(lock.contains("coord = \"org.scala-lang:scala3-library_3:3.6.4\""), "got: {lock}");
+    assert!(lock.contains("scope = \"compiler\""), "got: {lock}");
+    assert!(lock.contains("sha256 = "), "got: {lock}");
+
+    let output = run_sb_in(&work_dir, &["build", "--locked"]);
+    assert!(output.status.success(), "locked build failed: {}", stderr_of(&output));
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        toml.replace("dependencies = []", "dependencies = [\"com.lihaoyi::sourcecode:0.4.2\"]"),
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build", "--locked"]);
+    assert!(!output.status.success(), "locked build should fail with a stale lock");
+    assert!(stderr_of(&output).contains("out of date"), "got: {}", stderr_of(&output));
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Adding com.lihaoyi:sourcecode_3:0.4.2"),
+        "got: {}",
+        stderr_of(&output),
+    );
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("com.lihaoyi:sourcecode_3:0.4.2"), "got: {lock}");
+}
+
+#[test]
+fn conflict_resolution_strategies() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    // cats-effect-kernel 3.5.4 asks for cats-core 2.10.0
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let toml = toml.replace(
+        "dependencies = []",
+        "dependencies = [\"org.typelevel::cats-core:2.9.0\", \"org.typelevel::cats-effect-kernel:3.5.4\"]",
+    );
+    std::fs::write(work_dir.join("sb.toml"), &toml).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains(
+            "org.typelevel:cats-core_3:2.10.0 -> 2.9.0 (requested by org.typelevel:cats-effect-kernel_3:3.5.4)"
+        ),
+        "got: {}",
+        stderr_of(&output),
+    );
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("org.typelevel:cats-core_3:2.9.0"), "got: {lock}");
+
+    let toml = toml.replace("[project]", "[project]\nconflict-resolution = \"highest\"");
+    std::fs::write(work_dir.join("sb.toml"), toml).unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("org.typelevel:cats-core_3:2.9.0 -> 2.10.0 (requested by sb.toml)"),
+        "got: {}",
+        stderr_of(&output),
+    );
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("org.typelevel:cats-core_3:2.10.0"), "got: {lock}");
+}
+
+#[test]
+fn version_range_picks_highest_match() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        toml.replace("dependencies = []", "dependencies = [\"com.lihaoyi::sourcecode:[0.4.0,0.4.2)\"]"),
+    )
+    .unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("com.lihaoyi:sourcecode_3:0.4.1"), "got: {lock}");
+
+    // The chosen version is served from the lock and cache from now on
+    let output = run_sb_in(&work_dir, &["build", "--locked", "--offline"]);
+    assert!(output.status.success(), "locked build failed: {}", stderr_of(&output));
+}
+
+#[test]
+fn tree_and_why() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        toml.replace("dependencies = []", "dependencies = [\"org.typelevel::cats-core:2.10.0\"]"),
+    )
+    .unwrap();
+
+    let output = run_sb_in(&work_dir, &["tree"]);
+    assert!(output.status.success(), "tree failed: {}", stderr_of(&output));
+    let tree = stdout_of(&output);
+    assert!(tree.starts_with("firstproj 0.1.0\n"), "got: {tree}");
+    assert!(tree.contains("└── org.typelevel:cats-core_3:2.10.0"), "got: {tree}");
+    assert!(tree.contains("org.typelevel:cats-kernel_3:2.10.0"), "got: {tree}");
+
+    let output = run_sb_in(&work_dir, &["why", "cats-kernel"]);
+    assert!(output.status.success(), "why failed: {}", stderr_of(&output));
+    assert!(
+        stdout_of(&output).contains("org.typelevel:cats-core_3:2.10.0 > org.typelevel:cats-kernel_3:2.10.0"),
+        "got: {}",
+        stdout_of(&output),
+    );
+
+    let output = run_sb_in(&work_dir, &["why", "org.typelevel:cats-kernel_3", "--json"]);
+    assert!(output.status.success(), "why --json failed: {}", stderr_of(&output));
+    let json: serde_json::Value = serde_json::from_str(&stdout_of(&output)).expect("valid JSON");
+    assert_eq!(json[0]["module"], "org.typelevel:cats-kernel_3");
+    assert_eq!(json[0]["paths"][0][0]["from"], "sb.toml");
+
+    let output = run_sb_in(&work_dir, &["why", "does-not-exist"]);
+    assert!(!output.status.success(), "why should fail for an unknown dependency");
+}
+
+#[test]
+fn dependency_tables_scope_exclude_and_classifier() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let deps = r#"dependencies = [
+  { dep = "org.typelevel::cats-core:2.10.0", exclude = ["org.typelevel::cats-kernel"] },
+  { dep = "org.lwjgl:lwjgl:3.3.3", classifier = "natives-linux", transitive = false },
+  { dep = "com.lihaoyi::sourcecode:0.4.2", scope = "provided" },
+]"#;
+    std::fs::write(work_dir.join("sb.toml"), toml.replace("dependencies = []", deps)).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("org.typelevel:cats-core_3:2.10.0"), "got: {lock}");
+    assert!(!lock.contains("cats-kernel"), "cats-kernel should be excluded: {lock}");
+    assert!(lock.contains("coord = \"org.lwjgl:lwjgl:3.3.3:natives-linux\""), "got: {lock}");
+    assert!(!lock.contains("coord = \"org.lwjgl:lwjgl:3.3.3\""), "got: {lock}");
+    assert!(
+        lock.contains("coord = \"com.lihaoyi:sourcecode_3:0.4.2\"\nscope = \"provided\""),
+        "got: {lock}"
+    );
+
+    // Provided dependencies stay out of the assembly
+    let output = run_sb_in(&work_dir, &["asm"]);
+    assert!(output.status.success(), "asm failed: {}", stderr_of(&output));
+    let jar = std::fs::File::open(work_dir.join(".sb/firstproj-0.1.0-assembly.jar")).unwrap();
+    let names: Vec<String> = zip::ZipArchive::new(jar).unwrap().file_names().map(String::from).collect();
+    assert!(!names.iter().any(|n| n.starts_with("sourcecode/")), "got: {names:?}");
+}
+
+#[test]
+fn mixed_scala_cross_versions_fail() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let deps = r#"dependencies = [
+  { dep = "com.lihaoyi::sourcecode:0.4.2", cross = "2.13" },
+  "com.lihaoyi::sourcecode:0.4.2",
+]"#;
+    std::fs::write(work_dir.join("sb.toml"), toml.replace("dependencies = []", deps)).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!output.status.success(), "build should fail with _2.13 and _3 artifacts");
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("conflicting Scala versions"), "got: {stderr}");
+    assert!(stderr.contains("com.lihaoyi:sourcecode_2.13:0.4.2"), "got: {stderr}");
+    assert!(stderr.contains("com.lihaoyi:sourcecode_3:0.4.2"), "got: {stderr}");
+
+    // Only the 2.13 artifact is fine
+    let deps = r#"dependencies = [{ dep = "com.lihaoyi::sourcecode:0.4.2", cross = "2.13" }]"#;
+    std::fs::write(work_dir.join("sb.toml"), toml.replace("dependencies = []", deps)).unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("com.lihaoyi:sourcecode_2.13:0.4.2"), "got: {lock}");
+}
+
+#[test]
+fn full_scala_version_dependency() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let deps = r#"dependencies = [{ dep = "org.wartremover:::wartremover:3.3.1", scope = "provided" }]"#;
+    std::fs::write(work_dir.join("sb.toml"), toml.replace("dependencies = []", deps)).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("org.wartremover:wartremover_3.6.4:3.3.1"), "got: {lock}");
+
+    let output = run_sb_in(&work_dir, &["update", "org.wartremover:::wartremover"]);
+    assert!(output.status.success(), "update failed: {}", stderr_of(&output));
+
+    // Without a version
+    let deps = r#"dependencies = ["org.wartremover:::wartremover"]"#;
+    std::fs::write(work_dir.join("sb.toml"), toml.replace("dependencies = []", deps)).unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!output.status.success(), "build should fail without a version");
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("expected org:::name:version"), "got: {stderr}");
+}
+
+#[test]
+fn package_writes_reproducible_jars() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let output = run_sb_in(&work_dir, &["package", "--class-path"]);
+    assert!(output.status.success(), "package failed: {}", stderr_of(&output));
+    let sb_dir = work_dir.join(".sb");
+    let jar_path = sb_dir.join("firstproj-0.1.0.jar");
+    for name in ["firstproj-0.1.0.jar", "firstproj-0.1.0-sources.jar", "firstproj-0.1.0-javadoc.jar"] {
+        assert!(sb_dir.join(name).exists(), "{name} not written");
+    }
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&jar_path).unwrap()).unwrap();
+    let names: Vec<String> = archive.file_names().map(String::from).collect();
+    assert!(names.iter().any(|n| n == "hello.class"), "got: {names:?}");
+    assert!(names.iter().any(|n| n.ends_with(".tasty")), "got: {names:?}");
+    // Only the project's own classes
+    assert!(!names.iter().any(|n| n.starts_with("scala/")), "got: {names:?}");
+    let mut manifest = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("META-INF/MANIFEST.MF").unwrap(), &mut manifest).unwrap();
+    assert!(manifest.contains("Implementation-Title: firstproj\r\n"), "got: {manifest}");
+    assert!(manifest.contains("Implementation-Version: 0.1.0\r\n"), "got: {manifest}");
+    assert!(manifest.contains("Main-Class: hello\r\n"), "got: {manifest}");
+    assert!(manifest.contains("Class-Path: lib/"), "got: {manifest}");
+
+    // Runnable with the copied dependencies
+    let output = Command::new("java").arg("-jar").arg(&jar_path).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Hello from sb!"));
+
+    // Same classes, same bytes, even with newer timestamps
+    let first = std::fs::read(&jar_path).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    for entry in walkdir::WalkDir::new(sb_dir.join("classes")).into_iter().filter_map(Result::ok) {
+        if entry.file_type().is_file() {
+            let bytes = std::fs::read(entry.path()).unwrap();
+            std::fs::write(entry.path(), bytes).unwrap();
+        }
+    }
+    let output = run_sb_in(&work_dir, &["package", "--class-path"]);
+    assert!(output.status.success(), "package failed: {}", stderr_of(&output));
+    assert_eq!(first, std::fs::read(&jar_path).unwrap(), "JAR is not reproducible");
+}
+
+#[test]
+fn asm_merges_service_files() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    // Both register an org.slf4j.spi.SLF4JServiceProvider and ship a module-info.class
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap().replace(
+        "dependencies = []",
+        "dependencies = [\"org.slf4j:slf4j-simple:2.0.13\", \"ch.qos.logback:logback-classic:1.5.6\"]",
+    );
+    std::fs::write(work_dir.join("sb.toml"), &toml).unwrap();
+    let service = "META-INF/services/org.slf4j.spi.SLF4JServiceProvider";
+    // The same provider as slf4j-simple's
+    std::fs::create_dir_all(work_dir.join("src/main/resources/META-INF/services")).unwrap();
+    std::fs::write(work_dir.join("src/main/resources").join(service), "org.slf4j.simple.SimpleServiceProvider\n").unwrap();
+
+    let output = run_sb_in(&work_dir, &["asm", "--report"]);
+    assert!(output.status.success(), "asm failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains(&format!("{service} (concat)")), "got: {}", stdout_of(&output));
+
+    let jar = std::fs::File::open(work_dir.join(".sb/firstproj-0.1.0-assembly.jar")).unwrap();
+    let mut archive = zip::ZipArchive::new(jar).unwrap();
+    assert!(!archive.file_names().any(|n| n.ends_with("module-info.class")));
+    let mut providers = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name(service).unwrap(), &mut providers).unwrap();
+    assert_eq!(providers.matches("org.slf4j.simple.SimpleServiceProvider").count(), 1, "got: {providers}");
+    assert!(providers.contains("ch.qos.logback.classic.spi.LogbackServiceProvider"), "got: {providers}");
+
+    // A configured rule wins over the built-in ones
+    let strict = format!("{toml}\n[[assembly.merge]]\npath = \"META-INF/services/*\"\nstrategy = \"error\"\n");
+    std::fs::write(work_dir.join("sb.toml"), strict).unwrap();
+    let output = run_sb_in(&work_dir, &["asm"]);
+    assert!(!output.status.success(), "asm should fail on differing service files");
+    assert!(stderr_of(&output).contains(service), "got: {}", stderr_of(&output));
+}
+
+#[test]
+fn asm_merge_strategies() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    // pekko-actor ships a reference.conf of its own
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml"))
+        .unwrap()
+        .replace("dependencies = []", "dependencies = [\"org.apache.pekko::pekko-actor:1.0.2\"]");
+    std::fs::create_dir_all(work_dir.join("src/main/resources")).unwrap();
+    std::fs::write(work_dir.join("src/main/resources/reference.conf"), "sbtest.greeting = hello\n").unwrap();
+    let assemble = |rule: &str| {
+        std::fs::write(work_dir.join("sb.toml"), format!("{toml}{rule}")).unwrap();
+        let output = run_sb_in(&work_dir, &["asm"]);
+        assert!(output.status.success(), "asm failed: {}", stderr_of(&output));
+        let jar = std::fs::File::open(work_dir.join(".sb/firstproj-0.1.0-assembly.jar")).unwrap();
+        let mut archive = zip::ZipArchive::new(jar).unwrap();
+        let names: Vec<String> = archive.file_names().map(String::from).collect();
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut content).unwrap();
+            content
+        };
+        let conf = names.iter().any(|n| n == "reference.conf").then(|| read("reference.conf"));
+        let renamed = names.iter().find(|n| n.starts_with("reference.conf_")).map(|n| (n.clone(), read(n)));
+        (conf, renamed)
+    };
+    let rule = |strategy: &str| format!("\n[[assembly.merge]]\npath = \"reference.conf\"\nstrategy = \"{strategy}\"\n");
+
+    // Concatenated by default, the project's copy first
+    let (conf, _) = assemble("");
+    let conf = conf.expect("reference.conf missing");
+    assert!(conf.starts_with("sbtest.greeting = hello\n"), "got: {conf}");
+    assert!(conf.contains("pekko {"), "got: {conf}");
+
+    let (conf, _) = assemble(&rule("last"));
+    let conf = conf.expect("reference.conf missing");
+    assert!(!conf.contains("sbtest.greeting"), "got: {conf}");
+    assert!(conf.contains("pekko {"), "got: {conf}");
+
+    // The project's copy keeps its name, the dependency's gets the JAR name
+    let (conf, renamed) = assemble(&rule("rename"));
+    assert_eq!(conf.as_deref(), Some("sbtest.greeting = hello\n"));
+    let (name, content) = renamed.expect("renamed copy missing");
+    assert_eq!(name, "reference.conf_pekko-actor_3-1.0.2");
+    assert!(content.contains("pekko {"), "got: {content}");
+
+    let (conf, renamed) = assemble(&rule("discard"));
+    assert!(conf.is_none() && renamed.is_none());
+}
+
+#[test]
+fn asm_relocates_shaded_packages() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml"))
+        .unwrap()
+        .replace("dependencies = []", "dependencies = [\"com.google.guava:guava:33.2.1-jre\"]");
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        format!("{toml}\n[assembly.shade]\n\"com.google.common.**\" = \"myapp.shaded.guava.@1\"\n"),
+    )
+    .unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Main.scala"),
+        "import com.google.common.base.Joiner\n\n@main def hello(): Unit =\n  \
+         println(Joiner.on(\", \").join(java.util.List.of(\"a\", \"b\")))\n  \
+         println(Class.forName(\"com.google.common.base.Joiner\").getName)\n  \
+         println(\"com.google.common.base not found\")\n  \
+         println(\"see Lcom/google/common/base/Joiner;\")\n",
+    )
+    .unwrap();
+
+    let output = run_sb_in(&work_dir, &["asm"]);
+    assert!(output.status.success(), "asm failed: {}", stderr_of(&output));
+    let jar_path = work_dir.join(".sb/firstproj-0.1.0-assembly.jar");
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&jar_path).unwrap()).unwrap();
+    assert!(archive.by_name("myapp/shaded/guava/base/Joiner.class").is_ok());
+    assert!(!archive.file_names().any(|n| n.starts_with("com/google/common/")));
+
+    let output = Command::new("java").arg("-jar").arg(&jar_path).output().unwrap();
+    assert!(output.status.success(), "java -jar failed: {}", stderr_of(&output));
+    // Only class names are relocated, not messages that mention them
+    assert_eq!(
+        stdout_of(&output),
+        "a, b\nmyapp.shaded.guava.base.Joiner\ncom.google.common.base not found\nsee Lcom/google/common/base/Joiner;\n"
+    );
+}
+
+#[test]
+fn run_finds_entry_points_in_tasty() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(work_dir.join("sb.toml"), toml.replace("main-class = \"hello\"\n", "")).unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Main.scala"),
+        "package app\n\n@main def hello(): Unit = println(\"hello\")\n",
+    )
+    .unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Server.scala"),
+        "package app\n\nobject Server:\n  def main(args: Array[String]): Unit = println(\"server \" + args.mkString)\n",
+    )
+    .unwrap();
+
+    // Two entry points and no terminal to choose from
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(!output.status.success(), "run should not pick an entry point");
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("app.Server (src/main/scala/Server.scala)"), "got: {stderr}");
+    assert!(stderr.contains("app.hello"), "got: {stderr}");
+
+    let output = run_sb_in(&work_dir, &["run", "--main", "Server", "x"]);
+    assert!(output.status.success(), "run --main failed: {}", stderr_of(&output));
+    assert_eq!(stdout_of(&output), "server x\n");
+
+    let output = run_sb_in(&work_dir, &["run", "--main", "helo"]);
+    assert!(!output.status.success());
+    assert!(stderr_of(&output).contains("did you mean 'app.hello'?"), "got: {}", stderr_of(&output));
+    let output = run_sb_in(&work_dir, &["asm", "--main", "Sever"]);
+    assert!(!output.status.success(), "asm should not write a JAR for a misspelled main class");
+    assert!(stderr_of(&output).contains("did you mean 'app.Server'?"), "got: {}", stderr_of(&output));
+    assert!(!work_dir.join(".sb/firstproj-0.1.0-assembly.jar").exists());
+
+    // Not an entry point of the project, but a class on the runtime classpath
+    let output = run_sb_in(&work_dir, &["run", "--main", "scala.util.Properties"]);
+    assert!(output.status.success(), "run --main failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Scala library version"), "got: {}", stdout_of(&output));
+
+    let output = run_sb_in(&work_dir, &["asm", "--main", "app.Server"]);
+    assert!(output.status.success(), "asm failed: {}", stderr_of(&output));
+    let jar = std::fs::File::open(work_dir.join(".sb/firstproj-0.1.0-assembly.jar")).unwrap();
+    let mut manifest = String::new();
+    let mut archive = zip::ZipArchive::new(jar).unwrap();
+    std::io::Read::read_to_string(&mut archive.by_name("META-INF/MANIFEST.MF").unwrap(), &mut manifest).unwrap();
+    assert!(manifest.contains("Main-Class: app.Server"), "got: {manifest}");
+
+    // With one entry point left, it runs without being named
+    std::fs::remove_file(work_dir.join("src/main/scala/Server.scala")).unwrap();
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert_eq!(stdout_of(&output), "hello\n");
+}
+
+#[test]
+fn native_reports_missing_native_image() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (tmp, work_dir) = setup_project("firstproj");
+    let missing = tmp.path().join("graalvm/bin/native-image");
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        format!("{toml}\n[native]\nnative-image = \"{}\"\n", missing.display()),
+    )
+    .unwrap();
+
+    for args in [&["native"][..], &["native", "agent"]] {
+        let output = run_sb_in(&work_dir, args);
+        assert!(!output.status.success(), "sb {args:?} should fail");
+        assert!(stderr_of(&output).contains("native-image not found at"), "got: {}", stderr_of(&output));
+    }
+    assert!(!work_dir.join(".sb/native").exists());
+}
+
+#[test]
+fn resources_are_filtered_copied_and_packaged() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(work_dir.join("sb.toml"), format!("{toml}\n[resources]\nfilter = [\"*.properties\"]\n")).unwrap();
+    let resources = work_dir.join("src/main/resources");
+    std::fs::create_dir_all(resources.join("data")).unwrap();
+    std::fs::write(resources.join("app.properties"), "version=${project.version}\nhome=${HOME}\n").unwrap();
+    std::fs::write(resources.join("data/greeting.txt"), "hi ${project.version}\n").unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Main.scala"),
+        "@main def hello(): Unit =\n  \
+         val text = scala.io.Source.fromResource(\"app.properties\").mkString\n  \
+         print(text + scala.io.Source.fromResource(\"data/greeting.txt\").mkString)\n",
+    )
+    .unwrap();
+
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert_eq!(stdout_of(&output), "version=0.1.0\nhome=${HOME}\nhi ${project.version}\n");
+
+    // Unchanged resources are not copied again; deleted ones are removed
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!stderr_of(&output).contains("resource"), "got: {}", stderr_of(&output));
+    std::fs::remove_file(resources.join("data/greeting.txt")).unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(!work_dir.join(".sb/resources/data").exists());
+
+    let output = run_sb_in(&work_dir, &["asm"]);
+    assert!(output.status.success(), "asm failed: {}", stderr_of(&output));
+    let jar = std::fs::File::open(work_dir.join(".sb/firstproj-0.1.0-assembly.jar")).unwrap();
+    let mut archive = zip::ZipArchive::new(jar).unwrap();
+    let mut properties = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("app.properties").unwrap(), &mut properties).unwrap();
+    assert_eq!(properties, "version=0.1.0\nhome=${HOME}\n");
+}
+
+/// A library project in package `sbtestpublish`, published as `group_id::publib:<version>`.
+fn setup_library(group_id: &str, version: &str) -> (tempfile::TempDir, PathBuf) {
+    let (tmp, work_dir) = setup_project("firstproj");
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        format!(
+            "[project]\nname = \"publib\"\nversion = \"{version}\"\nscala-version = \"3.6.4\"\n\
+             group-id = \"{group_id}\"\nlicense = \"Apache-2.0\"\nscm = \"https://example.com/publib\"\n\
+             dependencies = [\"com.lihaoyi::sourcecode:0.4.2\"]\n"
+        ),
+    )
+    .unwrap();
+    std::fs::remove_file(work_dir.join("src/main/scala/Main.scala")).unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Greeting.scala"),
+        "package sbtestpublish\n\nobject Greeting:\n  def text: String = \"Hello from publib\"\n",
+    )
+    .unwrap();
+    (tmp, work_dir)
+}
+
+#[test]
+fn publish_local_then_depend() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (lib_tmp, lib_dir) = setup_library("com.example.sbtest", "0.1.0");
+    // Publish into a cache of its own rather than the user's
+    let cache = lib_tmp.path().join("cache");
+    let run_sb_cached = |dir: &Path, args: &[&str]| {
+        Command::new(sb_binary())
+            .args(args)
+            .current_dir(dir)
+            .env("XDG_CACHE_HOME", &cache)
+            .output()
+            .expect("failed to run sb")
+    };
+
+    let output = run_sb_cached(&lib_dir, &["publish-local"]);
+    assert!(output.status.success(), "publish-local failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Published com.example.sbtest:publib_3:0.1.0"),
+        "got: {}",
+        stderr_of(&output)
+    );
+
+    let (_tmp, work_dir) = setup_project("firstproj");
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let toml = toml.replace("dependencies = []", "dependencies = [\"com.example.sbtest::publib:0.1.0\"]");
+    std::fs::write(work_dir.join("sb.toml"), toml).unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Main.scala"),
+        "@main def hello(): Unit =\n  println(sbtestpublish.Greeting.text)\n",
+    )
+    .unwrap();
+
+    let output = run_sb_cached(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Hello from publib"), "got: {}", stdout_of(&output));
+    // Its dependencies come from the generated POM
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("com.lihaoyi:sourcecode_3:0.4.2"), "got: {lock}");
+}
+
+#[test]
+fn publish_uploads_files_checksums_and_metadata() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_library("com.example.sbtest", "0.2.0");
+
+    let output = run_sb_in(&work_dir, &["publish", "--repo", "nope"]);
+    assert!(!output.status.success());
+    assert!(stderr_of(&output).contains("no repository named 'nope'"), "got: {}", stderr_of(&output));
+
+    // A repository that accepts every PUT and has no files yet
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut request_line = String::new();
+                    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    let mut len = 0;
+                    let mut auth = String::new();
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let header = header.trim_end();
+                        if header.is_empty() {
+                            break;
+                        }
+                        let (name, value) = header.split_once(':').unwrap();
+                        match name.to_ascii_lowercase().as_str() {
+                            "content-length" => len = value.trim().parse().unwrap(),
+                            "authorization" => auth = value.trim().to_string(),
+                            _ => {}
+                        }
+                    }
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+                    let mut parts = request_line.split_whitespace();
+                    let method = parts.next().unwrap().to_string();
+                    let path = parts.next().unwrap().to_string();
+                    let status = if method == "PUT" { "201 Created" } else { "404 Not Found" };
+                    let _ = tx.send((method, path, auth, body));
+                    let _ = stream.write_all(format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n").as_bytes());
+                }
+            });
+        }
+    });
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(
+        work_dir.join("sb.toml"),
+        format!(
+            "{toml}\n[[repositories]]\nname = \"internal\"\nurl = \"http://127.0.0.1:{port}/releases\"\n\
+             token-env = \"SB_TEST_PUBLISH_TOKEN\"\n"
+        ),
+    )
+    .unwrap();
+    let output = Command::new(sb_binary())
+        .args(["publish", "--repo", "internal"])
+        .current_dir(&work_dir)
+        .env("SB_TEST_PUBLISH_TOKEN", "secret")
+        .output()
+        .expect("failed to run sb");
+    assert!(output.status.success(), "publish failed: {}", stderr_of(&output));
+
+    let requests: Vec<(String, String, String, Vec<u8>)> = rx.try_iter().collect();
+    let puts: Vec<&str> = requests
+        .iter()
+        .filter(|(m, _, _, _)| m == "PUT")
+        .map(|(_, p, _, _)| p.as_str())
+        .collect();
+    let dir = "/releases/com/example/sbtest/publib_3";
+    let files = [
+        "publib_3-0.2.0.jar",
+        "publib_3-0.2.0-sources.jar",
+        "publib_3-0.2.0-javadoc.jar",
+        "publib_3-0.2.0.pom",
+    ];
+    for file in files {
+        for ext in ["", ".sha256", ".sha1", ".md5"] {
+            let path = format!("{dir}/0.2.0/{file}{ext}");
+            assert!(puts.contains(&path.as_str()), "{path} not uploaded: {puts:?}");
+        }
+    }
+    assert!(puts.contains(&format!("{dir}/maven-metadata.xml").as_str()), "got: {puts:?}");
+    assert!(requests.iter().all(|(_, _, auth, _)| auth == "Bearer secret"));
+
+    let body = |path: &str| {
+        let (_, _, _, body) = requests.iter().find(|(m, p, _, _)| m == "PUT" && p == path).unwrap();
+        String::from_utf8_lossy(body).to_string()
+    };
+    let pom = body(&format!("{dir}/0.2.0/publib_3-0.2.0.pom"));
+    assert!(pom.contains("<artifactId>sourcecode_3</artifactId>"), "got: {pom}");
+    assert!(pom.contains("<artifactId>scala3-library_3</artifactId>"), "got: {pom}");
+    assert!(pom.contains("<name>Apache-2.0</name>"), "got: {pom}");
+    assert!(pom.contains("<url>https://example.com/publib</url>"), "got: {pom}");
+    let metadata = body(&format!("{dir}/maven-metadata.xml"));
+    assert!(metadata.contains("<version>0.2.0</version>"), "got: {metadata}");
+}
+
+#[test]
+fn lockfile_checksum_mismatch_fails() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // Corrupt every recorded checksum and drop the cached classpath
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    let tampered: String = lock
+        .lines()
+        .map(|l| {
+            if l.starts_with("sha256 = ") {
+                format!("sha256 = \"{}\"\n", "0".repeat(64))
+            } else {
+                format!("{l}\n")
+            }
+        })
+        .collect();
+    std::fs::write(work_dir.join("sb.lock"), tampered).unwrap();
+    std::fs::remove_dir_all(work_dir.join(".sb")).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!output.status.success(), "build should fail on a checksum mismatch");
+    assert!(stderr_of(&output).contains("checksum mismatch"), "got: {}", stderr_of(&output));
+}
+
+/// Serve `files` by path over HTTP, with 404 for everything else. Returns the port.
+fn serve_files(files: Vec<(String, Vec<u8>)>) -> u16 {
+    use std::io::{BufRead, BufReader, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let files = std::sync::Arc::new(files);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let files = files.clone();
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                loop {
+                    let mut request_line = String::new();
+                    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim_end().is_empty() {
+                            break;
+                        }
+                    }
+                    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                    let response = match files.iter().find(|(p, _)| p == path) {
+                        Some((_, body)) => {
+                            let mut response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+                            response.extend_from_slice(body);
+                            response
+                        }
+                        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                    };
+                    let _ = stream.write_all(&response);
+                }
+            });
+        }
+    });
+    port
+}
+
+#[test]
+fn sidecar_checksums_are_verified() {
+    use sha1::{Digest, Sha1};
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (tmp, work_dir) = setup_project("firstproj");
+    let jar = b"not really a jar".to_vec();
+    let actual = format!("{:x}", Sha1::digest(&jar));
+    let pom = b"<project><modelVersion>4.0.0</modelVersion><groupId>com.example.sbtest</groupId>\
+<artifactId>checked</artifactId><version>1.0</version></project>"
+        .to_vec();
+    let dir = "/repo/com/example/sbtest/checked/1.0";
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let toml = toml.replace("dependencies = []", "dependencies = [\"com.example.sbtest:checked:1.0\"]");
+    // A cache of its own, so that a verified JAR is never reused without a check
+    let update = |port: u16, cache: &str| {
+        std::fs::write(
+            work_dir.join("sb.toml"),
+            format!("{toml}\n[[repositories]]\nname = \"fixture\"\nurl = \"http://127.0.0.1:{port}/repo\"\n"),
+        )
+        .unwrap();
+        Command::new(sb_binary())
+            .arg("update")
+            .current_dir(&work_dir)
+            .env("XDG_CACHE_HOME", tmp.path().join(cache))
+            .output()
+            .expect("failed to run sb")
+    };
+
+    let wrong = "0".repeat(40);
+    let port = serve_files(vec![
+        (format!("{dir}/checked-1.0.pom"), pom.clone()),
+        (format!("{dir}/checked-1.0.jar"), jar.clone()),
+        (format!("{dir}/checked-1.0.jar.sha1"), wrong.clone().into_bytes()),
+    ]);
+    let output = update(port, "mismatch-cache");
+    assert!(!output.status.success(), "update should fail on a checksum mismatch");
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("checksum mismatch for com.example.sbtest:checked:1.0"), "got: {stderr}");
+    assert!(stderr.contains(&format!("expected sha1 {wrong}, got {actual}")), "got: {stderr}");
+
+    // sha1sum style, with the file name after the hash
+    let port = serve_files(vec![
+        (format!("{dir}/checked-1.0.pom"), pom),
+        (format!("{dir}/checked-1.0.jar"), jar),
+        (format!("{dir}/checked-1.0.jar.sha1"), format!("{actual}  checked-1.0.jar\n").into_bytes()),
+    ]);
+    let output = update(port, "named-cache");
+    assert!(output.status.success(), "update failed: {}", stderr_of(&output));
+    let lock = std::fs::read_to_string(work_dir.join("sb.lock")).unwrap();
+    assert!(lock.contains("com.example.sbtest:checked:1.0"), "got: {lock}");
+}
+
+#[test]
+fn locked_build_without_lockfile_fails() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let output = run_sb_in(&work_dir, &["build", "--locked"]);
+    assert!(!output.status.success());
+    assert!(stderr_of(&output).contains("sb.lock is missing"), "got: {}", stderr_of(&output));
+}
+
+/// firstproj with an extra dependency that exists in no repository, so it is
+/// never in the local Maven cache and resolving it must hit the network.
+fn setup_uncached_dep(extra_toml: &str) -> (tempfile::TempDir, PathBuf) {
+    let (tmp, work_dir) = setup_project("firstproj");
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    let toml = toml.replace(
+        "dependencies = []",
+        "dependencies = [\"com.example.sbtest:does-not-exist:0.0.1\"]",
+    );
+    std::fs::write(work_dir.join("sb.toml"), format!("{toml}\n{extra_toml}")).unwrap();
+    (tmp, work_dir)
+}
+
+#[test]
+fn mirror_replaces_central() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_uncached_dep(
+        "[[repositories]]\nname = \"corp-mirror\"\nurl = \"http://127.0.0.1:1/maven\"\nmirror-of = \"*\"\n",
+    );
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!output.status.success());
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("corp-mirror"), "mirror was not used: {stderr}");
+    assert!(!stderr.contains("repo1.maven.org"), "central was not replaced: {stderr}");
+}
+
+#[test]
+fn user_config_repositories() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (tmp, work_dir) = setup_uncached_dep("");
+    let config_home = tmp.path().join("config");
+    std::fs::create_dir_all(config_home.join("sb")).unwrap();
+    std::fs::write(
+        config_home.join("sb/config.toml"),
+        "[[repositories]]\nname = \"nexus\"\nurl = \"http://127.0.0.1:1/nexus\"\n",
+    )
+    .unwrap();
+
+    let output = Command::new(sb_binary())
+        .arg("build")
+        .current_dir(&work_dir)
+        .env("XDG_CONFIG_HOME", &config_home)
+        .output()
+        .expect("failed to run sb");
+    assert!(!output.status.success());
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("nexus: HTTP GET http://127.0.0.1:1/nexus/"), "got: {stderr}");
+}
+
+const PRIVATE_REPOSITORY: &str =
+    "[[repositories]]\nname = \"private\"\nurl = \"https://maven.example.com\"\ntoken-env = \"SB_TEST_UNSET_TOKEN\"\n";
+
+fn run_sb_without_token(work_dir: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(sb_binary())
+        .args(args)
+        .current_dir(work_dir)
+        .env_remove("SB_TEST_UNSET_TOKEN")
+        .output()
+        .expect("failed to run sb")
+}
+
+#[test]
+fn repository_credentials_env_must_be_set() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // Everything is cached, so the repository is never contacted
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    std::fs::write(work_dir.join("sb.toml"), format!("{toml}\n{PRIVATE_REPOSITORY}")).unwrap();
+    let output = run_sb_without_token(&work_dir, &["build", "--locked"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // A dependency that is not cached is looked up there first
+    let (_tmp, work_dir) = setup_uncached_dep(PRIVATE_REPOSITORY);
+    let output = run_sb_without_token(&work_dir, &["build"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr_of(&output).contains("repository 'private': environment variable SB_TEST_UNSET_TOKEN is not set"),
+        "got: {}",
+        stderr_of(&output),
+    );
+}
+
+#[test]
+fn offline_build_ignores_repository_credentials() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    let toml = std::fs::read_to_string(work_dir.join("sb.toml")).unwrap();
+    // Also from a mirror, which takes the place of the private repository
+    let mirror = "[[repositories]]\nname = \"private-mirror\"\nurl = \"http://127.0.0.1:1/maven\"\nmirror-of = \"private\"\n";
+    std::fs::write(work_dir.join("sb.toml"), format!("{toml}\n{PRIVATE_REPOSITORY}{mirror}")).unwrap();
+    std::fs::remove_dir_all(work_dir.join(".sb")).unwrap();
+    std::fs::remove_file(work_dir.join("sb.lock")).unwrap();
+    let output = run_sb_without_token(&work_dir, &["build", "--offline"]);
+    assert!(output.status.success(), "offline build failed: {}", stderr_of(&output));
+}
+
+#[test]
+fn offline_build_uses_cache() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("firstproj");
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // Everything is cached now; resolve again from scratch without the network
+    std::fs::remove_dir_all(work_dir.join(".sb")).unwrap();
+    std::fs::remove_file(work_dir.join("sb.lock")).unwrap();
+    let output = run_sb_in(&work_dir, &["build", "--offline"]);
+    assert!(output.status.success(), "offline build failed: {}", stderr_of(&output));
+}
+
+#[test]
+fn offline_build_lists_missing_artifacts() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_uncached_dep("");
+
+    let output = Command::new(sb_binary())
+        .arg("build")
+        .current_dir(&work_dir)
+        .env("SB_OFFLINE", "1")
+        .output()
+        .expect("failed to run sb");
+    assert!(!output.status.success());
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("not in the local Maven cache"), "got: {stderr}");
+    assert!(stderr.contains("com.example.sbtest:does-not-exist:0.0.1 (pom)"), "got: {stderr}");
+    assert!(!stderr.contains("warning: failed to resolve POM"), "got: {stderr}");
+}
+
 #[test]
 fn neg_projects_fail() {
     let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -190,6 +1338,32 @@ fn neg_projects_fail() {
     }
 }
 
+#[test]
+fn diagnostics_summary_and_json() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/neg/type_mismatch");
+
+    let output = run_sb(&project, &["build"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 error in 1 file"), "missing summary: {stderr}");
+
+    let output = run_sb(&project, &["build", "--message-format=json"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let diags: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("each stdout line is a JSON diagnostic"))
+        .collect();
+    assert_eq!(diags.len(), 1, "got: {stdout}");
+    let d = &diags[0];
+    assert_eq!(d["severity"], "error");
+    assert_eq!(d["code"], "E007");
+    assert_eq!(d["line"], 2);
+    assert!(d["file"].as_str().unwrap().ends_with("Main.scala"), "got: {d}");
+    assert!(d["message"].as_str().unwrap().contains("Required: Int"), "got: {d}");
+}
+
 #[test]
 fn tasty_roundtrip() {
     let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -490,7 +1664,7 @@ fn incremental_whitespace_change() {
 }
 
 // ====================================================================
-// 10. Add new method to existing object (API change)
+// 10. Add new method to existing object: unused, so no cascade
 // ====================================================================
 #[test]
 fn incremental_add_method_api_change() {
@@ -505,11 +1679,10 @@ fn incremental_add_method_api_change() {
 
     let output = run_sb_in(&work_dir, &["build"]);
     assert!(output.status.success(), "build failed: {}", stderr_of(&output));
-    // Middle changed; API change may or may not cascade depending on hash
-    assert!(
-        !stderr_of(&output).contains("Nothing to compile"),
-        "expected recompilation",
-    );
+    // Top doesn't use the new method, so only Middle is recompiled
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("Compiling 1 source file"), "expected 1 file, got: {stderr}");
+    assert!(!stderr.contains("round 2"), "unused method should not cascade, got: {stderr}");
 
     // Still runs
     let output = run_sb_in(&work_dir, &["run"]);
@@ -933,6 +2106,223 @@ fn incremental_comment_change() {
     assert!(stderr_of(&output).contains("Nothing to compile"));
 }
 
+// ====================================================================
+// 26. Dependencies across packages are tracked by fully qualified name
+// ====================================================================
+#[test]
+fn incremental_tracks_dependencies_across_packages() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_project("packages");
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // A class with the same simple name in another package
+    let other = work_dir.join("src/main/scala/other");
+    std::fs::create_dir_all(&other).unwrap();
+    std::fs::write(other.join("Person.scala"), "package com.example.other\n\ncase class Person(id: Int)\n").unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // Nothing uses it, so its API change recompiles it alone
+    std::fs::write(
+        other.join("Person.scala"),
+        "package com.example.other\n\ncase class Person(id: Int, tag: String = \"\")\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(stderr_of(&output).contains("Compiling 1 source file..."), "got: {}", stderr_of(&output));
+    assert!(!stderr_of(&output).contains("round 2"), "got: {}", stderr_of(&output));
+
+    // com.example.model.Person is constructed in com.example and used in com.example.service
+    std::fs::write(
+        work_dir.join("src/main/scala/Person.scala"),
+        "package com.example.model\n\ncase class Person(name: String, age: Int, title: String = \"Mr/Ms\"):\n  \
+         def greetingName: String = if age < 18 then name else s\"$title $name\"\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Compiling 2 source files (round 2)"),
+        "expected Greeter.scala and Main.scala to be recompiled, got: {}",
+        stderr_of(&output),
+    );
+
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Hello, Mr/Ms Alice!"), "got: {}", stdout_of(&output));
+}
+
+// ====================================================================
+// 27. Changing a member a dependent uses cascades to it
+// ====================================================================
+#[test]
+fn incremental_used_method_change_cascades() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental();
+
+    // Add a defaulted parameter to Middle.process, which Top calls
+    std::fs::write(
+        work_dir.join("src/main/scala/Middle.scala"),
+        "object Middle:\n  def process(b: Base, prefix: String = \"Base\"): String = s\"$prefix: ${b.x}\"\n",
+    ).unwrap();
+
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Compiling 1 source file (round 2)"),
+        "expected Top to be recompiled, got: {}",
+        stderr_of(&output),
+    );
+
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Base: 42"));
+}
+
+fn setup_incremental_inline() -> (tempfile::TempDir, PathBuf) {
+    let (tmp, work_dir) = setup_project("incremental_inline");
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "initial build failed: {}", stderr_of(&output));
+    (tmp, work_dir)
+}
+
+// ====================================================================
+// 28. Inline body change recompiles the callers it was inlined into
+// ====================================================================
+#[test]
+fn incremental_inline_body_change_cascades() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental_inline();
+
+    std::fs::write(
+        work_dir.join("src/main/scala/Config.scala"),
+        "object Config:\n  inline def greeting: String = \"Hi\"\n  transparent inline def limit: Int = 5\n  \
+         def unrelated: Int = 1\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Compiling 1 source file (round 2)"),
+        "expected Main.scala to be recompiled, got: {}",
+        stderr_of(&output),
+    );
+
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Hi 5"), "got: {}", stdout_of(&output));
+
+    // The body of a method that isn't inline is still not part of the API
+    std::fs::write(
+        work_dir.join("src/main/scala/Config.scala"),
+        "object Config:\n  inline def greeting: String = \"Hi\"\n  transparent inline def limit: Int = 5\n  \
+         def unrelated: Int = 2\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(!stderr_of(&output).contains("round 2"), "got: {}", stderr_of(&output));
+}
+
+// ====================================================================
+// 29. Macro implementation change recompiles the macro's callers
+// ====================================================================
+#[test]
+fn incremental_macro_implementation_change_cascades() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental_inline();
+
+    std::fs::write(
+        work_dir.join("src/main/scala/Macros.scala"),
+        "import scala.quoted.*\n\nobject Macros:\n  inline def answer: Int = ${ answerImpl }\n\n  \
+         def answerImpl(using Quotes): Expr[Int] = Expr(43)\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Compiling 1 source file (round 2)"),
+        "expected Main.scala to be recompiled, got: {}",
+        stderr_of(&output),
+    );
+
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("answer 43"), "got: {}", stdout_of(&output));
+}
+
+// ====================================================================
+// 30. New subclass of a sealed trait rechecks matches on it
+// ====================================================================
+#[test]
+fn incremental_sealed_subclass_rechecks_matches() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental_inline();
+
+    std::fs::write(
+        work_dir.join("src/main/scala/Shape.scala"),
+        "sealed trait Shape\ncase class Circle(r: Int) extends Shape\ncase class Square(side: Int) extends Shape\n\
+         case class Triangle(base: Int) extends Shape\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("(round 2)"), "expected Describe.scala to be recompiled, got: {stderr}");
+    assert!(stderr.contains("not be exhaustive"), "expected an exhaustivity warning, got: {stderr}");
+
+    // Removing it again makes the match exhaustive
+    std::fs::write(
+        work_dir.join("src/main/scala/Shape.scala"),
+        "sealed trait Shape\ncase class Circle(r: Int) extends Shape\ncase class Square(side: Int) extends Shape\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    let stderr = stderr_of(&output);
+    assert!(stderr.contains("(round 2)"), "expected Describe.scala to be recompiled, got: {stderr}");
+    assert!(!stderr.contains("not be exhaustive"), "got: {stderr}");
+}
+
+// ====================================================================
+// 31. A failed round leaves the last good build intact
+// ====================================================================
+#[test]
+fn incremental_failure_rolls_back() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental();
+    let middle = work_dir.join("src/main/scala/Middle.scala");
+    let original = std::fs::read_to_string(&middle).unwrap();
+    let classes = work_dir.join(".sb/classes");
+    let middle_class = std::fs::read(classes.join("Middle$.class")).unwrap();
+
+    // Round 1 fails
+    std::fs::write(&middle, "object Middle:\n  def process(b: Base): String = \n").unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!output.status.success(), "expected the build to fail");
+    assert_eq!(std::fs::read(classes.join("Middle$.class")).unwrap(), middle_class);
+
+    // Round 1 succeeds, then Top fails to compile against the new Middle in round 2
+    std::fs::write(&middle, "object Middle:\n  def process(n: Int): String = s\"Base: $n\"\n").unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(!output.status.success(), "expected the build to fail");
+    assert!(stderr_of(&output).contains("(round 2)"), "got: {}", stderr_of(&output));
+    assert_eq!(std::fs::read(classes.join("Middle$.class")).unwrap(), middle_class);
+    assert!(!work_dir.join(".sb/backup").exists());
+    assert!(!work_dir.join(".sb/staging").exists());
+
+    // The last good build is still current
+    std::fs::write(&middle, original).unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(stderr_of(&output).contains("Nothing to compile"), "got: {}", stderr_of(&output));
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Base: 42"));
+}
+
 #[test]
 fn scala2_rejected() {
     let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
@@ -945,3 +2335,56 @@ fn scala2_rejected() {
         "expected Scala 3 error message, got: {stderr}",
     );
 }
+
+// ====================================================================
+// 32. Changing a member used through an instance cascades, other members do not
+// ====================================================================
+#[test]
+fn incremental_instance_member_change_cascades() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_tmp, work_dir) = setup_incremental();
+    let base = work_dir.join("src/main/scala/Base.scala");
+
+    // Middle calls label on a Base it is given, so it selects it on an instance
+    std::fs::write(
+        &base,
+        "case class Base(x: Int):\n  def label: String = s\"Base: $x\"\n  def unused: Int = 0\n",
+    )
+    .unwrap();
+    std::fs::write(
+        work_dir.join("src/main/scala/Middle.scala"),
+        "object Middle:\n  def process(b: Base): String = b.label.toString\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+
+    // A new result type for label
+    std::fs::write(
+        &base,
+        "case class Base(x: Int):\n  def label: CharSequence = s\"Base: $x\"\n  def unused: Int = 0\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(
+        stderr_of(&output).contains("Compiling 1 source file (round 2)"),
+        "expected Middle to be recompiled, got: {}",
+        stderr_of(&output),
+    );
+    assert!(!stderr_of(&output).contains("round 3"), "got: {}", stderr_of(&output));
+
+    // Nothing uses this one
+    std::fs::write(
+        &base,
+        "case class Base(x: Int):\n  def label: CharSequence = s\"Base: $x\"\n  def unused: Long = 0\n",
+    )
+    .unwrap();
+    let output = run_sb_in(&work_dir, &["build"]);
+    assert!(output.status.success(), "build failed: {}", stderr_of(&output));
+    assert!(!stderr_of(&output).contains("round 2"), "got: {}", stderr_of(&output));
+
+    let output = run_sb_in(&work_dir, &["run"]);
+    assert!(output.status.success(), "run failed: {}", stderr_of(&output));
+    assert!(stdout_of(&output).contains("Base: 42"));
+}
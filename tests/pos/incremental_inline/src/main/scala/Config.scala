@@ -0,0 +1,4 @@
+object Config:
+  inline def greeting: String = "Hello"
+  transparent inline def limit: Int = 3
+  def unrelated: Int = 1
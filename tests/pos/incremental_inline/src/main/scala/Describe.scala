@@ -0,0 +1,4 @@
+object Describe:
+  def describe(s: Shape): String = s match
+    case Circle(r) => s"circle $r"
+    case Square(side) => s"square $side"
@@ -0,0 +1,6 @@
+import scala.quoted.*
+
+object Macros:
+  inline def answer: Int = ${ answerImpl }
+
+  def answerImpl(using Quotes): Expr[Int] = Expr(42)
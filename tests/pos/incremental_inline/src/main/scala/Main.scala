@@ -0,0 +1,4 @@
+@main def inlineMain(): Unit =
+  println(s"${Config.greeting} ${Config.limit}")
+  println(s"answer ${Macros.answer}")
+  println(Describe.describe(Circle(1)))
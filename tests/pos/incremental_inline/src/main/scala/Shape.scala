@@ -0,0 +1,3 @@
+sealed trait Shape
+case class Circle(r: Int) extends Shape
+case class Square(side: Int) extends Shape
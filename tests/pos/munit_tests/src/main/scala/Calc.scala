@@ -0,0 +1,3 @@
+object Calc:
+  def add(a: Int, b: Int): Int = a + b
+  def mul(a: Int, b: Int): Int = a * b
@@ -0,0 +1,7 @@
+class CalcSuite extends munit.FunSuite:
+  test("add") {
+    assertEquals(Calc.add(2, 3), 5)
+  }
+  test("mul") {
+    assertEquals(Calc.mul(2, 3), 6)
+  }
@@ -0,0 +1,4 @@
+import core.Rect
+
+@main def appMain(): Unit =
+  println(s"Area: ${Rect(3, 4).area}")
@@ -0,0 +1,4 @@
+package core
+
+case class Rect(w: Int, h: Int):
+  def area: Int = w * h